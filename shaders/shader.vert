@@ -2,11 +2,13 @@
 
 layout (location=0) in vec3 position;
 layout (location=1) in vec3 normal;
-layout (location=2) in mat4 model_matrix;
-layout (location=6) in mat4 inverse_model_matrix;
-layout (location=10) in vec3 colour;
-layout (location=11) in float metallic;
-layout (location=12) in float roughness;
+layout (location=2) in vec3 vertex_colour;
+layout (location=3) in vec2 uv;
+layout (location=4) in mat4 model_matrix;
+layout (location=8) in mat4 inverse_model_matrix;
+layout (location=12) in vec3 colour;
+layout (location=13) in float metallic;
+layout (location=14) in float roughness;
 
 layout(set=0, binding=0) uniform UniformBufferObject {
   mat4 view_matrix;
@@ -19,14 +21,16 @@ layout (location=2) out vec4 fragWorldPos;
 layout (location=3) out vec3 cameraPos;
 layout (location=4) out float fragMetallic;
 layout (location=5) out float fragRoughness;
+layout (location=6) out vec2 fragUv;
 
 void main() {
   fragWorldPos = model_matrix * vec4(position,1.0);
   gl_Position = ubo.projection_matrix * ubo.view_matrix * model_matrix * vec4(position,1.0);
-  fragColor = colour;
+  fragColor = colour * vertex_colour;
   fragNormal = transpose(mat3(inverse_model_matrix)) * normal;
   fragMetallic = metallic;
   fragRoughness = roughness;
+  fragUv = uv;
 
   cameraPos =
     - ubo.view_matrix[3][0] * vec3(ubo.view_matrix[0][0], ubo.view_matrix[1][0], ubo.view_matrix[2][0])
@@ -0,0 +1,11 @@
+#version 450
+
+layout (constant_id = 0) const float brightness = 1.0;
+
+layout (location = 0) in vec2 fragUv;
+
+layout (location = 0) out vec4 fragColor;
+
+void main() {
+  fragColor = vec4(fragUv * brightness, 0.0, 1.0);
+}
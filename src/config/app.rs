@@ -3,6 +3,11 @@ pub struct AppConfig {
   pub version: u32,
   pub width: u32,
   pub height: u32,
+  pub frames_in_flight: u32,
+  /// Clamped to what the surface supports, fewer images lower latency, more smooth out frame times
+  pub desired_image_count: u32,
+  /// Caps the redraw rate, `None` renders as fast as possible
+  pub max_fps: Option<u32>,
 }
 
 impl Default for AppConfig {
@@ -12,6 +17,9 @@ impl Default for AppConfig {
       version: 1,
       width: 800,
       height: 600,
+      frames_in_flight: 2,
+      desired_image_count: 3,
+      max_fps: None,
     }
   }
 }
\ No newline at end of file
@@ -31,9 +31,16 @@ pub struct RendererConfig<'a> {
   pub instance_extensions: Vec<&'a std::ffi::CStr>,
   pub instance_next: Vec<Box<dyn vk::ExtendsInstanceCreateInfo + Send>>,
   pub device_extensions: Vec<&'a std::ffi::CStr>,
+  pub optional_instance_extensions: Vec<&'a std::ffi::CStr>,
+  pub optional_device_extensions: Vec<&'a std::ffi::CStr>,
   pub device_features: vk::PhysicalDeviceFeatures,
   pub debug: bool,
   pub debug_log_level: vk::DebugUtilsMessageSeverityFlagsEXT,
+  pub reverse_z: bool,
+  pub color_attachment_ops: Option<AttachmentOps>,
+  pub depth_attachment_ops: Option<AttachmentOps>,
+  pub device_preference: DevicePreference,
+  pub pipeline_cache_dir: Option<std::path::PathBuf>,
 }
 
 impl<'a> RendererConfig<'a> {
@@ -42,11 +49,98 @@ impl<'a> RendererConfig<'a> {
     self
   }
 
+  pub fn add_instance_extension(mut self, extension: &'a std::ffi::CStr) -> Self {
+    self.instance_extensions.push(extension);
+    self
+  }
+
+  /// Only enabled if the driver supports it instead of failing initialization
+  pub fn add_optional_instance_extension(mut self, extension: &'a std::ffi::CStr) -> Self {
+    self.optional_instance_extensions.push(extension);
+    self
+  }
+
+  pub fn add_device_extension(mut self, extension: &'a std::ffi::CStr) -> Self {
+    self.device_extensions.push(extension);
+    self
+  }
+
+  /// Only enabled if the physical device supports it instead of failing initialization
+  pub fn add_optional_device_extension(mut self, extension: &'a std::ffi::CStr) -> Self {
+    self.optional_device_extensions.push(extension);
+    self
+  }
+
   pub fn set_debug(mut self, debug: bool) -> Self {
     self.debug = debug;
     self
   }
 
+  /// Maps the near plane to depth 1 and the far plane to 0 for better depth precision, cameras
+  /// need the matching `Camera::set_reverse_z`. The depth image then has to be cleared to 0
+  /// instead of 1 once frames are recorded
+  pub fn set_reverse_z(mut self, reverse_z: bool) -> Self {
+    self.reverse_z = reverse_z;
+    self
+  }
+
+  /// Defaults to clearing and storing the swap chain image, `LOAD` keeps what an earlier pass drew.
+  /// With `LOAD` the render pass expects the image in `PRESENT_SRC_KHR`, freshly acquired images
+  /// are still `UNDEFINED` until they were presented once, so the first frame of every image has
+  /// to be transitioned or cleared by an earlier pass
+  pub fn set_color_attachment_ops(
+    mut self,
+    load_op: vk::AttachmentLoadOp,
+    store_op: vk::AttachmentStoreOp,
+  ) -> Self {
+    self.color_attachment_ops = Some(AttachmentOps::new(load_op, store_op));
+    self
+  }
+
+  /// Defaults to clearing the depth image and discarding it after the pass. With `LOAD` the depth
+  /// image has to be in `DEPTH_STENCIL_ATTACHMENT_OPTIMAL` already, it starts out `UNDEFINED`
+  pub fn set_depth_attachment_ops(
+    mut self,
+    load_op: vk::AttachmentLoadOp,
+    store_op: vk::AttachmentStoreOp,
+  ) -> Self {
+    self.depth_attachment_ops = Some(AttachmentOps::new(load_op, store_op));
+    self
+  }
+
+  /// Where pipeline caches are loaded from and saved to on shutdown, defaults to `cache`
+  pub fn set_pipeline_cache_dir(mut self, dir: impl Into<std::path::PathBuf>) -> Self {
+    self.pipeline_cache_dir = Some(dir.into());
+    self
+  }
+
+  pub(crate) fn get_pipeline_cache_dir(&self) -> std::path::PathBuf {
+    self
+      .pipeline_cache_dir
+      .clone()
+      .unwrap_or_else(|| "cache".into())
+  }
+
+  pub(crate) fn get_color_attachment_ops(&self) -> AttachmentOps {
+    self.color_attachment_ops.unwrap_or(AttachmentOps::new(
+      vk::AttachmentLoadOp::CLEAR,
+      vk::AttachmentStoreOp::STORE,
+    ))
+  }
+
+  pub(crate) fn get_depth_attachment_ops(&self) -> AttachmentOps {
+    self.depth_attachment_ops.unwrap_or(AttachmentOps::new(
+      vk::AttachmentLoadOp::CLEAR,
+      vk::AttachmentStoreOp::DONT_CARE,
+    ))
+  }
+
+  /// Overridden by the `VULKAN_DEVICE` environment variable if it is set
+  pub fn set_device_preference(mut self, device_preference: DevicePreference) -> Self {
+    self.device_preference = device_preference;
+    self
+  }
+
   pub fn set_debug_log_level(mut self, level: LogLevel) -> Self {
     self.debug_log_level = match level {
       LogLevel::Info => {
@@ -71,6 +165,63 @@ impl<'a> RendererConfig<'a> {
   }
 }
 
+/// Which kind of physical device is picked if several are available, other kinds are still used as
+/// a fallback
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DevicePreference {
+  #[default]
+  Discrete,
+  Integrated,
+  /// A CPU implementation like lavapipe, useful for running without a GPU in CI
+  Software,
+}
+
+impl DevicePreference {
+  /// Reads `discrete`, `integrated` or `software` from the `VULKAN_DEVICE` environment variable
+  pub fn from_env() -> Option<Self> {
+    match std::env::var("VULKAN_DEVICE").ok()?.to_lowercase().as_str() {
+      "discrete" => Some(Self::Discrete),
+      "integrated" => Some(Self::Integrated),
+      "software" => Some(Self::Software),
+      _ => None,
+    }
+  }
+
+  fn device_type(self) -> vk::PhysicalDeviceType {
+    match self {
+      Self::Discrete => vk::PhysicalDeviceType::DISCRETE_GPU,
+      Self::Integrated => vk::PhysicalDeviceType::INTEGRATED_GPU,
+      Self::Software => vk::PhysicalDeviceType::CPU,
+    }
+  }
+
+  /// Higher is better, the preferred type always wins and hardware beats software otherwise
+  pub(crate) fn rank(self, device_type: vk::PhysicalDeviceType) -> u32 {
+    if device_type == self.device_type() {
+      return 5;
+    }
+    match device_type {
+      vk::PhysicalDeviceType::DISCRETE_GPU => 4,
+      vk::PhysicalDeviceType::INTEGRATED_GPU => 3,
+      vk::PhysicalDeviceType::VIRTUAL_GPU => 2,
+      vk::PhysicalDeviceType::CPU => 1,
+      _ => 0,
+    }
+  }
+}
+
+#[derive(Clone, Copy)]
+pub struct AttachmentOps {
+  pub load_op: vk::AttachmentLoadOp,
+  pub store_op: vk::AttachmentStoreOp,
+}
+
+impl AttachmentOps {
+  pub fn new(load_op: vk::AttachmentLoadOp, store_op: vk::AttachmentStoreOp) -> Self {
+    Self { load_op, store_op }
+  }
+}
+
 pub enum PipelineType {
   Graphics(GraphicsPipelineConfig),
   Compute(ComputePipelineConfig),
@@ -81,8 +232,15 @@ pub struct GraphicsPipelineConfig {
   pub shaders: Vec<ShaderConfig>,
   pub input: Vec<ShaderInputBindings>,
   pub topology: vk::PrimitiveTopology,
+  pub primitive_restart: bool,
+  pub patch_control_points: u32,
   pub viewport_size: (u32, u32),
   pub descriptor_sets: Vec<DescriptorSet>,
+  pub push_constants: Vec<PushConstant>,
+  pub depth_bias: Option<DepthBias>,
+  pub depth_test: bool,
+  pub depth_write: bool,
+  pub base_pipeline: Option<String>,
 }
 
 impl GraphicsPipelineConfig {
@@ -96,11 +254,36 @@ impl GraphicsPipelineConfig {
       shaders: Vec::new(),
       input: Vec::new(),
       topology,
+      primitive_restart: false,
+      patch_control_points: 3,
       viewport_size,
       descriptor_sets: Vec::new(),
+      push_constants: Vec::new(),
+      depth_bias: None,
+      depth_test: true,
+      depth_write: true,
+      base_pipeline: None,
     }
   }
 
+  /// Draws one triangle covering the whole viewport without any vertex input, meant for post
+  /// effects. The fragment shader receives the screen uv at location 0. It is drawn with 3 vertices
+  /// and no vertex buffer bound
+  pub fn fullscreen_triangle(
+    name: String,
+    fragment_shader: ShaderConfig,
+    viewport_size: (u32, u32),
+  ) -> Self {
+    Self::new(name, vk::PrimitiveTopology::TRIANGLE_LIST, viewport_size)
+      .add_shader(ShaderConfig::new(
+        ShaderType::Vertex,
+        vk_shader_macros::include_glsl!("./shaders/fullscreen.vert").to_vec(),
+      ))
+      .add_shader(fragment_shader)
+      .set_depth_test(false)
+      .set_depth_write(false)
+  }
+
   pub fn add_shader(mut self, shader: ShaderConfig) -> Self {
     self.shaders.push(shader);
     self
@@ -115,12 +298,88 @@ impl GraphicsPipelineConfig {
     self.descriptor_sets.push(descriptor_set);
     self
   }
+
+  pub fn add_push_constant(mut self, push_constant: PushConstant) -> Self {
+    self.push_constants.push(push_constant);
+    self
+  }
+
+  /// Restarts strip and fan primitives at the maximum index value. List and patch topologies are
+  /// rejected when the pipeline is created
+  pub fn set_primitive_restart(mut self, primitive_restart: bool) -> Self {
+    self.primitive_restart = primitive_restart;
+    self
+  }
+
+  /// Only used with tessellation shaders, which also need the `PATCH_LIST` topology. Has to be
+  /// between 1 and the device's maxTessellationPatchSize
+  pub fn set_patch_control_points(mut self, patch_control_points: u32) -> Self {
+    self.patch_control_points = patch_control_points;
+    self
+  }
+
+  pub fn set_depth_bias(mut self, depth_bias: DepthBias) -> Self {
+    self.depth_bias = Some(depth_bias);
+    self
+  }
+
+  /// Disabling both is useful for overlays and UI drawn on top of the scene
+  pub fn set_depth_test(mut self, depth_test: bool) -> Self {
+    self.depth_test = depth_test;
+    self
+  }
+
+  pub fn set_depth_write(mut self, depth_write: bool) -> Self {
+    self.depth_write = depth_write;
+    self
+  }
+
+  /// Pipelines with a vertex but without a fragment shader only write depth, e.g. for shadow maps or
+  /// a depth prepass
+  pub fn is_depth_only(&self) -> bool {
+    let has_stage = |stage| self.shaders.iter().any(|shader| shader.type_ == stage);
+    has_stage(vk::ShaderStageFlags::VERTEX) && !has_stage(vk::ShaderStageFlags::FRAGMENT)
+  }
+
+  pub fn has_tessellation(&self) -> bool {
+    self.shaders.iter().any(|shader| {
+      shader.type_.intersects(
+        vk::ShaderStageFlags::TESSELLATION_CONTROL | vk::ShaderStageFlags::TESSELLATION_EVALUATION,
+      )
+    })
+  }
+
+  /// Creates this pipeline as a derivative of the named pipeline, which lets the driver reuse work
+  /// for variants that only differ slightly. The base has to be added before this pipeline
+  pub fn set_base_pipeline(mut self, name: String) -> Self {
+    self.base_pipeline = Some(name);
+    self
+  }
+}
+
+/// Polygon offset applied to the depth of rasterized fragments, used against z-fighting in shadow
+/// maps and decals. A non zero clamp requires the depthBiasClamp device feature
+pub struct DepthBias {
+  pub constant_factor: f32,
+  pub slope_factor: f32,
+  pub clamp: f32,
+}
+
+impl DepthBias {
+  pub fn new(constant_factor: f32, slope_factor: f32, clamp: f32) -> Self {
+    Self {
+      constant_factor,
+      slope_factor,
+      clamp,
+    }
+  }
 }
 
 pub struct ComputePipelineConfig {
   pub name: String,
   pub shader: ShaderConfig,
   pub descriptor_sets: Vec<DescriptorSet>,
+  pub push_constants: Vec<PushConstant>,
 }
 
 impl ComputePipelineConfig {
@@ -130,8 +389,10 @@ impl ComputePipelineConfig {
       shader: ShaderConfig {
         type_: vk::ShaderStageFlags::COMPUTE,
         code: Vec::new(),
+        specialization: Vec::new(),
       },
       descriptor_sets: Vec::new(),
+      push_constants: Vec::new(),
     }
   }
 
@@ -144,11 +405,17 @@ impl ComputePipelineConfig {
     self.descriptor_sets.push(descriptor_set);
     self
   }
+
+  pub fn add_push_constant(mut self, push_constant: PushConstant) -> Self {
+    self.push_constants.push(push_constant);
+    self
+  }
 }
 
 pub struct ShaderConfig {
   pub type_: vk::ShaderStageFlags,
   pub code: Vec<u32>,
+  pub specialization: Vec<(u32, SpecConstant)>,
 }
 
 impl ShaderConfig {
@@ -158,8 +425,56 @@ impl ShaderConfig {
       ShaderType::Fragment => vk::ShaderStageFlags::FRAGMENT,
       ShaderType::Compute => vk::ShaderStageFlags::COMPUTE,
       ShaderType::Geometry => vk::ShaderStageFlags::GEOMETRY,
+      ShaderType::TessellationControl => vk::ShaderStageFlags::TESSELLATION_CONTROL,
+      ShaderType::TessellationEvaluation => vk::ShaderStageFlags::TESSELLATION_EVALUATION,
     };
-    Self { type_, code }
+    Self {
+      type_,
+      code,
+      specialization: Vec::new(),
+    }
+  }
+
+  /// Sets the value of the `constant_id` specialization constant when the pipeline is created
+  pub fn add_specialization(mut self, constant_id: u32, value: SpecConstant) -> Self {
+    self.specialization.push((constant_id, value));
+    self
+  }
+
+  pub(crate) fn specialization_data(&self) -> (Vec<vk::SpecializationMapEntry>, Vec<u8>) {
+    let mut entries = Vec::with_capacity(self.specialization.len());
+    let mut data = Vec::new();
+    for (constant_id, value) in &self.specialization {
+      let bytes = value.to_bytes();
+      entries.push(
+        vk::SpecializationMapEntry::default()
+          .constant_id(*constant_id)
+          .offset(data.len() as u32)
+          .size(bytes.len()),
+      );
+      data.extend_from_slice(&bytes);
+    }
+    (entries, data)
+  }
+}
+
+#[derive(Clone, Copy)]
+pub enum SpecConstant {
+  Bool(bool),
+  Int(i32),
+  UInt(u32),
+  Float(f32),
+}
+
+impl SpecConstant {
+  /// Bools are passed as 32 bit `VkBool32` values
+  fn to_bytes(self) -> [u8; 4] {
+    match self {
+      SpecConstant::Bool(value) => (value as u32).to_ne_bytes(),
+      SpecConstant::Int(value) => value.to_ne_bytes(),
+      SpecConstant::UInt(value) => value.to_ne_bytes(),
+      SpecConstant::Float(value) => value.to_ne_bytes(),
+    }
   }
 }
 
@@ -168,6 +483,8 @@ pub enum ShaderType {
   Fragment,
   Compute,
   Geometry,
+  TessellationControl,
+  TessellationEvaluation,
 }
 
 pub struct ShaderInputBindings {
@@ -233,3 +550,118 @@ impl Descriptor {
     }
   }
 }
+
+/// A range of push constant bytes visible to the given stages, the end of every range has to stay
+/// within the device's `maxPushConstantsSize`. Offset and size have to be multiples of 4
+pub struct PushConstant {
+  pub stage: vk::ShaderStageFlags,
+  pub offset: u32,
+  pub size: u32,
+}
+
+impl PushConstant {
+  pub fn new(stage: vk::ShaderStageFlags, offset: u32, size: u32) -> Self {
+    Self {
+      stage,
+      offset,
+      size,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use ash::vk;
+
+  use super::{DevicePreference, GraphicsPipelineConfig, ShaderConfig, ShaderType, SpecConstant};
+
+  #[test]
+  fn specialization_data_packs_constants() {
+    let shader = ShaderConfig::new(ShaderType::Fragment, vec![])
+      .add_specialization(0, SpecConstant::Bool(true))
+      .add_specialization(3, SpecConstant::Int(-2))
+      .add_specialization(7, SpecConstant::Float(1.5));
+
+    let (entries, data) = shader.specialization_data();
+
+    let layout: Vec<_> = entries
+      .iter()
+      .map(|entry| (entry.constant_id, entry.offset, entry.size))
+      .collect();
+    assert_eq!(layout, vec![(0, 0, 4), (3, 4, 4), (7, 8, 4)]);
+    let expected: Vec<u8> = [
+      1u32.to_ne_bytes(),
+      (-2i32).to_ne_bytes(),
+      1.5f32.to_ne_bytes(),
+    ]
+    .concat();
+    assert_eq!(data, expected);
+  }
+
+  #[test]
+  fn fullscreen_triangle_has_no_vertex_input() {
+    let pipeline = GraphicsPipelineConfig::fullscreen_triangle(
+      "post".to_string(),
+      ShaderConfig::new(ShaderType::Fragment, vec![]),
+      (800, 600),
+    );
+
+    assert!(pipeline.input.is_empty());
+    assert_eq!(pipeline.topology, vk::PrimitiveTopology::TRIANGLE_LIST);
+    let stages: Vec<_> = pipeline.shaders.iter().map(|shader| shader.type_).collect();
+    assert_eq!(
+      stages,
+      vec![vk::ShaderStageFlags::VERTEX, vk::ShaderStageFlags::FRAGMENT]
+    );
+    assert!(!pipeline.shaders[0].code.is_empty());
+    assert!(!pipeline.depth_test);
+    assert!(!pipeline.depth_write);
+  }
+
+  #[test]
+  fn depth_only_needs_vertex_without_fragment_shader() {
+    let pipeline = GraphicsPipelineConfig::new(
+      "depth".to_string(),
+      vk::PrimitiveTopology::TRIANGLE_LIST,
+      (1, 1),
+    );
+    assert!(!pipeline.is_depth_only());
+
+    let pipeline = pipeline.add_shader(ShaderConfig::new(ShaderType::Vertex, vec![]));
+    assert!(pipeline.is_depth_only());
+
+    let pipeline = pipeline.add_shader(ShaderConfig::new(ShaderType::Fragment, vec![]));
+    assert!(!pipeline.is_depth_only());
+  }
+
+  #[test]
+  fn preferred_device_type_ranks_highest() {
+    let device_types = [
+      vk::PhysicalDeviceType::DISCRETE_GPU,
+      vk::PhysicalDeviceType::INTEGRATED_GPU,
+      vk::PhysicalDeviceType::VIRTUAL_GPU,
+      vk::PhysicalDeviceType::CPU,
+      vk::PhysicalDeviceType::OTHER,
+    ];
+    for preference in [
+      DevicePreference::Discrete,
+      DevicePreference::Integrated,
+      DevicePreference::Software,
+    ] {
+      let preferred = preference.rank(preference.device_type());
+      for device_type in device_types {
+        if device_type != preference.device_type() {
+          assert!(preference.rank(device_type) < preferred, "{preference:?}");
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn hardware_ranks_above_software_fallback() {
+    let rank = |device_type| DevicePreference::Integrated.rank(device_type);
+    assert!(rank(vk::PhysicalDeviceType::DISCRETE_GPU) > rank(vk::PhysicalDeviceType::VIRTUAL_GPU));
+    assert!(rank(vk::PhysicalDeviceType::VIRTUAL_GPU) > rank(vk::PhysicalDeviceType::CPU));
+    assert!(rank(vk::PhysicalDeviceType::CPU) > rank(vk::PhysicalDeviceType::OTHER));
+  }
+}
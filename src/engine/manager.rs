@@ -1,31 +1,50 @@
-use std::thread::{self, JoinHandle};
+use std::{
+  sync::Arc,
+  thread::{self, JoinHandle},
+};
 
-use crate::{config::EngineConfig, util::signal::Signal};
+use crate::{
+  config::EngineConfig,
+  scene::Scene,
+  util::{mutator::Mutator, signal::Signal},
+};
 
-use super::window::Window;
+use super::{window::Window, RendererInfo, Updater};
 
 pub trait Manager {
-  fn init(config: EngineConfig) -> Self;
+  fn init(config: EngineConfig, scene: Arc<Scene>, updater: Updater) -> Self;
   fn run(self);
 }
 
 pub struct ClientManager {
   window_handle: JoinHandle<()>,
   app_run: Signal,
+  renderer_info: Mutator<Option<RendererInfo>>,
+}
+
+impl ClientManager {
+  pub(crate) fn renderer_info(&self) -> Option<RendererInfo> {
+    self.renderer_info.get().clone()
+  }
 }
 
 impl Manager for ClientManager {
-  fn init(config: EngineConfig) -> Self {
+  fn init(config: EngineConfig, scene: Arc<Scene>, updater: Updater) -> Self {
     let window_ready = Signal::new();
     let app_run = Signal::new();
 
     let thread_window_ready = window_ready.clone();
     let thread_app_run = app_run.clone();
+    let renderer_info = Mutator::new(None);
+    let thread_renderer_info = renderer_info.clone();
 
     let window_handle = thread::spawn(move || {
       Window::init(
         config.app,
         config.vulkan,
+        scene,
+        updater,
+        thread_renderer_info,
         thread_window_ready,
         thread_app_run,
       )
@@ -37,6 +56,7 @@ impl Manager for ClientManager {
     ClientManager {
       window_handle,
       app_run,
+      renderer_info,
     }
   }
 
@@ -50,7 +70,7 @@ impl Manager for ClientManager {
 pub struct ServerManager {}
 
 impl Manager for ServerManager {
-  fn init(config: EngineConfig) -> Self {
+  fn init(config: EngineConfig, _scene: Arc<Scene>, _updater: Updater) -> Self {
     println!("Server manager is initialized");
     ServerManager {}
   }
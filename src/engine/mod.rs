@@ -1,6 +1,7 @@
-use std::marker::PhantomData;
+use std::{ffi::CString, marker::PhantomData, sync::Arc};
 
 use manager::{ClientManager, Manager, ServerManager};
+pub use updater::Updater;
 
 use crate::{config::EngineConfig, error::EngineBuildError, scene::Scene};
 
@@ -11,7 +12,8 @@ mod renderer;
 
 pub struct Engine<S, M: Manager> {
   state: S,
-  scene: Scene,
+  scene: Arc<Scene>,
+  updater: Updater,
   manager: M,
 }
 
@@ -23,12 +25,27 @@ pub struct EngineBuilder<S, M: Manager> {
 }
 
 impl<S, M: Manager> Engine<S, M> {
+  /// The scene shared with the window thread, keep a clone to despawn game objects while running
+  pub fn scene(&self) -> Arc<Scene> {
+    self.scene.clone()
+  }
+
+  /// Updates the scene every frame, keep a clone to pause it while running
+  pub fn updater(&self) -> Updater {
+    self.updater.clone()
+  }
+
   pub fn run(self) {
     self.manager.run();
   }
 }
 
 impl<S> Engine<S, ClientManager> {
+  /// `None` until the window thread has initialized Vulkan
+  pub fn renderer_info(&self) -> Option<RendererInfo> {
+    self.manager.renderer_info()
+  }
+
   pub fn builder_client() -> EngineBuilder<S, ClientManager> {
     EngineBuilder {
       state: None,
@@ -67,10 +84,54 @@ impl<S, M: Manager> EngineBuilder<S, M> {
   }
 
   pub fn build(self) -> Result<Engine<S, M>, EngineBuildError> {
+    let scene = Arc::new(self.scene.ok_or(EngineBuildError::SceneMissing)?);
+    let updater = Updater::new();
     Ok(Engine {
       state: self.state.ok_or(EngineBuildError::StateMissing)?,
-      scene: self.scene.ok_or(EngineBuildError::SceneMissing)?,
-      manager: M::init(self.config.unwrap_or_default())
+      manager: M::init(
+        self.config.unwrap_or_default(),
+        scene.clone(),
+        updater.clone(),
+      ),
+      scene,
+      updater,
     })
   }
-}
\ No newline at end of file
+}
+
+/// What the renderer ended up using on this machine
+#[derive(Debug, Clone)]
+pub struct RendererInfo {
+  instance_extensions: Vec<CString>,
+  device_extensions: Vec<CString>,
+  swap_chain_image_count: u32,
+}
+
+impl RendererInfo {
+  pub(crate) fn new(
+    instance_extensions: Vec<CString>,
+    device_extensions: Vec<CString>,
+    swap_chain_image_count: u32,
+  ) -> Self {
+    Self {
+      instance_extensions,
+      device_extensions,
+      swap_chain_image_count,
+    }
+  }
+
+  /// Required instance extensions plus the optional ones the driver supports
+  pub fn instance_extensions(&self) -> &[CString] {
+    &self.instance_extensions
+  }
+
+  /// Required device extensions plus the optional ones the physical device supports
+  pub fn device_extensions(&self) -> &[CString] {
+    &self.device_extensions
+  }
+
+  /// The number of swap chain images the driver created for `AppConfig::desired_image_count`
+  pub fn swap_chain_image_count(&self) -> u32 {
+    self.swap_chain_image_count
+  }
+}
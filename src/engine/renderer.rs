@@ -1,20 +1,76 @@
 use std::sync::Arc;
 
-use crate::{scene::{components::mesh_renderer::MeshRenderer, game_object::GameObject, Scene}, util::mutator::Mutator};
+use glam as g;
+
+use crate::{
+  scene::{
+    components::{
+      camera::Camera, mesh_renderer::MeshRenderer, transform::Transform, visibility::Visibility,
+    },
+    game_object::GameObject,
+    material::Material,
+    mesh::Mesh,
+    Scene,
+  },
+  util::mutator::Mutator,
+};
+
+pub struct CameraUpload {
+  pub view_matrix: g::Mat4,
+  pub projection_matrix: g::Mat4,
+}
 
 pub struct Renderer {
   tracked_game_objects: Vec<Mutator<GameObject>>,
   scene: Arc<Scene>,
   previous_frame: Vec<(u32, u32)>,
+  invalid_transforms: Vec<u32>,
+  meshes: Vec<Mesh>,
+  materials: Vec<Material>,
 }
 
 impl Renderer {
+  /// Registers `Mesh::cube` and the materials of the scene, so their ids match `Scene::add_material`
   pub fn init(scene: Arc<Scene>) -> Self {
-    Renderer {
-      tracked_game_objects: Vec::new(),
+    let materials = scene.materials().to_vec();
+    let mut renderer = Renderer {
+      tracked_game_objects: scene.game_objects().clone(),
       scene,
       previous_frame: Vec::new(),
+      invalid_transforms: Vec::new(),
+      meshes: Vec::new(),
+      materials: Vec::new(),
+    };
+    renderer.register_mesh(Mesh::cube());
+    for material in materials {
+      renderer.register_material(material);
     }
+    renderer
+  }
+
+  /// Returns the id to reference the mesh with in a `MeshRenderer`
+  pub fn register_mesh(&mut self, mesh: Mesh) -> u32 {
+    self.meshes.push(mesh);
+    self.meshes.len() as u32 - 1
+  }
+
+  /// Returns the id to reference the material with in a `MeshRenderer`
+  pub fn register_material(&mut self, material: Material) -> u32 {
+    self.materials.push(material);
+    self.materials.len() as u32 - 1
+  }
+
+  pub fn get_mesh(&self, id: u32) -> Option<&Mesh> {
+    self.meshes.get(id as usize)
+  }
+
+  pub fn get_material(&self, id: u32) -> Option<&Material> {
+    self.materials.get(id as usize)
+  }
+
+  /// Ids of the game objects skipped in the last update because their transform is not valid
+  pub fn invalid_transforms(&self) -> &[u32] {
+    &self.invalid_transforms
   }
 
   pub fn add_game_object(&mut self, game_object: Mutator<GameObject>) {
@@ -25,19 +81,309 @@ impl Renderer {
     self.tracked_game_objects.retain(|x| x.get().id() != id);
   }
 
-  pub fn update(&mut self) {
-    let mut to_render = Vec::new();
+  /// Keeps the projection of every tracked camera in line with the new swap chain extent
+  pub fn resize(&mut self, width: u32, height: u32) {
+    if width == 0 || height == 0 {
+      return;
+    }
+
     for game_object in self.tracked_game_objects.iter() {
-      let game_object = game_object.get();
-      let mesh_renderer = game_object.get_component::<MeshRenderer>();
-      if let Some(mesh_renderer) = mesh_renderer {
-        to_render.push((mesh_renderer.mesh_id(), mesh_renderer.material_id())); 
+      let mut game_object = game_object.get_mut();
+      if let Some(camera) = game_object.get_component_mut::<Camera>() {
+        camera.set_aspect_ratio(width as f32 / height as f32);
       }
     }
+  }
+
+  /// Returns the matrices of the first camera that changed since the last update, they have to be
+  /// uploaded before the frame is drawn
+  pub fn update(&mut self) -> Option<CameraUpload> {
+    self
+      .tracked_game_objects
+      .retain(|game_object| !game_object.get().is_despawned());
+
+    let camera_upload = self.take_camera_changes();
+    let to_render = self.gather_renderables();
 
     if self.previous_frame != to_render {
       self.previous_frame = to_render;
       println!("Render frame");
     }
+
+    camera_upload
+  }
+
+  /// Only changed cameras are locked for writing, everything else is checked under a read lock
+  fn take_camera_changes(&self) -> Option<CameraUpload> {
+    let mut camera_upload = None;
+    for game_object in self.tracked_game_objects.iter() {
+      let changed = game_object
+        .get()
+        .get_component::<Camera>()
+        .is_some_and(Camera::is_changed);
+      if !changed {
+        continue;
+      }
+
+      let mut game_object = game_object.get_mut();
+      if let Some(camera) = game_object.get_component_mut::<Camera>() {
+        camera.take_changed();
+        camera_upload.get_or_insert(CameraUpload {
+          view_matrix: camera.view_matrix(),
+          projection_matrix: camera.projection_matrix(),
+        });
+      }
+    }
+    camera_upload
+  }
+
+  /// (mesh id, material id) of every visible game object with a valid transform, objects that
+  /// reference an unregistered mesh or material are skipped
+  fn gather_renderables(&mut self) -> Vec<(u32, u32)> {
+    self.invalid_transforms.clear();
+    let mut to_render = Vec::new();
+    for game_object in self.tracked_game_objects.iter() {
+      let game_object = game_object.get();
+      let hidden = game_object
+        .get_component::<Visibility>()
+        .is_some_and(|visibility| !visibility.is_visible());
+      if hidden {
+        continue;
+      }
+
+      let invalid_transform = game_object
+        .get_component::<Transform>()
+        .is_some_and(|transform| !transform.is_valid());
+      if invalid_transform {
+        self.invalid_transforms.push(game_object.id());
+        continue;
+      }
+
+      let Some(mesh_renderer) = game_object.get_component::<MeshRenderer>() else {
+        continue;
+      };
+      let (mesh_id, material_id) = (mesh_renderer.mesh_id(), mesh_renderer.material_id());
+      if self.get_mesh(mesh_id).is_some() && self.get_material(material_id).is_some() {
+        to_render.push((mesh_id, material_id));
+      }
+    }
+    to_render
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::Arc;
+
+  use glam as g;
+
+  use super::Renderer;
+  use crate::{
+    scene::{
+      components::{
+        camera::Camera,
+        mesh_renderer::{MeshRenderer, CUBE_MESH, DEFAULT_MATERIAL},
+        transform::Transform,
+        visibility::Visibility,
+      },
+      game_object::GameObject,
+      material::Material,
+      mesh::Mesh,
+      Scene,
+    },
+    util::mutator::Mutator,
+  };
+
+  fn track(renderer: &mut Renderer, game_object: GameObject) -> Mutator<GameObject> {
+    let game_object = Mutator::new(game_object);
+    renderer.add_game_object(game_object.clone());
+    game_object
+  }
+
+  #[test]
+  fn registers_cube_and_default_material() {
+    let renderer = Renderer::init(Arc::new(Scene::new()));
+    let cube = renderer.get_mesh(CUBE_MESH).unwrap();
+    assert_eq!(cube.vertices(), Mesh::cube().vertices());
+    assert_eq!(cube.indices(), Mesh::cube().indices());
+    assert!(renderer.get_material(DEFAULT_MATERIAL).is_some());
+  }
+
+  fn unit_transform() -> Transform {
+    Transform::new(g::Vec3::ZERO, g::Quat::IDENTITY, g::Vec3::ONE)
+  }
+
+  #[test]
+  fn spawned_cube_renders_with_cube_mesh_and_default_material() {
+    let scene = Scene::new().spawn_cube(unit_transform(), g::Vec3::ONE);
+    let mut renderer = Renderer::init(Arc::new(Scene::new()));
+    renderer.add_game_object(scene.game_objects()[0].clone());
+    assert_eq!(
+      renderer.gather_renderables(),
+      vec![(CUBE_MESH, DEFAULT_MATERIAL)]
+    );
+  }
+
+  #[test]
+  fn coloured_cube_renders_with_scene_material() {
+    let colour = g::Vec3::new(0.0, 0.0, 1.0);
+    let scene = Scene::new()
+      .spawn_cube(unit_transform(), g::Vec3::ONE)
+      .spawn_cube(unit_transform(), colour);
+    let mut renderer = Renderer::init(Arc::new(scene));
+
+    let to_render = renderer.gather_renderables();
+
+    assert_eq!(to_render[0], (CUBE_MESH, DEFAULT_MATERIAL));
+    let (mesh_id, material_id) = to_render[1];
+    assert_eq!(mesh_id, CUBE_MESH);
+    assert_eq!(
+      renderer.get_material(material_id),
+      Some(&Material::from_colour(colour))
+    );
+  }
+
+  #[test]
+  fn skips_unregistered_meshes() {
+    let mut renderer = Renderer::init(Arc::new(Scene::new()));
+    track(
+      &mut renderer,
+      GameObject::new().add_component(MeshRenderer::new(CUBE_MESH + 1, DEFAULT_MATERIAL)),
+    );
+    assert!(renderer.gather_renderables().is_empty());
+  }
+
+  #[test]
+  fn invalid_transforms_are_not_rendered() {
+    let mut renderer = Renderer::init(Arc::new(Scene::new()));
+    let scales = [g::Vec3::ONE, g::Vec3::ZERO, g::Vec3::splat(f32::NAN)];
+    let game_objects: Vec<_> = scales
+      .into_iter()
+      .map(|scale| {
+        let transform = Transform::new(g::Vec3::ZERO, g::Quat::IDENTITY, scale);
+        track(
+          &mut renderer,
+          GameObject::new()
+            .add_component(transform)
+            .add_component(MeshRenderer::new(CUBE_MESH, DEFAULT_MATERIAL)),
+        )
+      })
+      .collect();
+    let ids: Vec<_> = game_objects
+      .iter()
+      .map(|game_object| game_object.get().id())
+      .collect();
+
+    renderer.update();
+
+    assert_eq!(renderer.previous_frame, vec![(CUBE_MESH, DEFAULT_MATERIAL)]);
+    assert_eq!(renderer.invalid_transforms(), &ids[1..]);
+  }
+
+  fn camera_renderer(aspect_ratio: f32) -> Renderer {
+    let camera = Camera::perspective(1.0, aspect_ratio, 0.1, 50.0);
+    let scene = Scene::new().add_game_object(GameObject::new().add_component(camera));
+    Renderer::init(Arc::new(scene))
+  }
+
+  fn projection_matrix(game_object: &Mutator<GameObject>) -> g::Mat4 {
+    let game_object = game_object.get();
+    game_object
+      .get_component::<Camera>()
+      .unwrap()
+      .projection_matrix()
+  }
+
+  #[test]
+  fn resize_updates_camera_aspect_ratio() {
+    let mut renderer = camera_renderer(16.0 / 9.0);
+    let camera = renderer.tracked_game_objects[0].clone();
+    let before = projection_matrix(&camera);
+
+    renderer.resize(800, 600);
+
+    let after = projection_matrix(&camera);
+    assert_ne!(after.x_axis.x, before.x_axis.x);
+    assert_eq!(after, g::Mat4::perspective_rh(1.0, 4.0 / 3.0, 0.1, 50.0));
+  }
+
+  #[test]
+  fn resize_ignores_minimized_window() {
+    let mut renderer = camera_renderer(16.0 / 9.0);
+    renderer.resize(0, 0);
+    assert_eq!(
+      projection_matrix(&renderer.tracked_game_objects[0]),
+      g::Mat4::perspective_rh(1.0, 16.0 / 9.0, 0.1, 50.0)
+    );
+  }
+
+  #[test]
+  fn despawned_game_objects_are_untracked() {
+    let mut renderer = Renderer::init(Arc::new(Scene::new()));
+    let game_object = track(
+      &mut renderer,
+      GameObject::new().add_component(MeshRenderer::new(CUBE_MESH, DEFAULT_MATERIAL)),
+    );
+
+    game_object.get_mut().despawn();
+    renderer.update();
+
+    assert!(renderer.tracked_game_objects.is_empty());
+  }
+
+  #[test]
+  fn hidden_game_objects_are_not_rendered() {
+    let mut renderer = Renderer::init(Arc::new(Scene::new()));
+    let materials: Vec<_> = (0..4)
+      .map(|_| renderer.register_material(Material::default()))
+      .collect();
+    for (i, material) in materials.iter().enumerate() {
+      track(
+        &mut renderer,
+        GameObject::new()
+          .add_component(Visibility::new(i % 2 == 0))
+          .add_component(MeshRenderer::new(CUBE_MESH, *material)),
+      );
+    }
+
+    assert_eq!(
+      renderer.gather_renderables(),
+      vec![(CUBE_MESH, materials[0]), (CUBE_MESH, materials[2])]
+    );
+  }
+
+  #[test]
+  fn camera_upload_only_on_changed_frames() {
+    let mut renderer = camera_renderer(16.0 / 9.0);
+    let camera = renderer.tracked_game_objects[0].clone();
+    let mut uploads = Vec::new();
+    for frame in 0..6 {
+      if frame == 2 {
+        let mut camera = camera.get_mut();
+        let camera = camera.get_component_mut::<Camera>().unwrap();
+        camera.set_position(g::Vec3::new(1.0, 2.0, 3.0));
+      }
+      if frame == 4 {
+        renderer.resize(800, 600);
+      }
+      if renderer.update().is_some() {
+        uploads.push(frame);
+      }
+    }
+    assert_eq!(uploads, vec![0, 2, 4]);
+  }
+
+  #[test]
+  fn camera_upload_carries_current_matrices() {
+    let mut renderer = camera_renderer(16.0 / 9.0);
+    let upload = renderer.update().unwrap();
+    assert_eq!(
+      upload.projection_matrix,
+      g::Mat4::perspective_rh(1.0, 16.0 / 9.0, 0.1, 50.0)
+    );
+    let camera = renderer.tracked_game_objects[0].get();
+    let camera = camera.get_component::<Camera>().unwrap();
+    assert_eq!(upload.view_matrix, camera.view_matrix());
+    assert!(!camera.is_changed());
   }
 }
@@ -1,13 +1,81 @@
-pub struct Updater {
+use std::sync::{
+  atomic::{AtomicBool, Ordering},
+  Arc,
+};
+
+use crate::scene::Scene;
 
+/// Updates the scene once per frame on the window thread, clones share the paused state so it can
+/// be changed from any thread
+#[derive(Clone, Default)]
+pub struct Updater {
+  paused: Arc<AtomicBool>,
 }
 
 impl Updater {
   pub fn new() -> Self {
-    Updater {}
+    Self::default()
+  }
+
+  /// While paused only components that run while paused are updated, e.g. for an editor pause or
+  /// a backgrounded app
+  pub fn set_paused(&self, paused: bool) {
+    self.paused.store(paused, Ordering::SeqCst);
+  }
+
+  pub fn is_paused(&self) -> bool {
+    self.paused.load(Ordering::SeqCst)
+  }
+
+  pub fn update(&self, scene: &Scene) {
+    if self.is_paused() {
+      scene.update_while_paused();
+    } else {
+      scene.update();
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::Arc;
+
+  use super::Updater;
+  use crate::scene::{game_object::GameObject, testing::HookCounter, Scene};
+
+  fn counted_scene(counter: &HookCounter) -> Arc<Scene> {
+    Arc::new(Scene::new().add_game_object(GameObject::new().add_component(counter.clone())))
   }
 
-  pub fn update(&self) {
-    println!("Updating");
+  #[test]
+  fn updates_scene() {
+    let counter = HookCounter::default();
+    Updater::new().update(&counted_scene(&counter));
+    assert_eq!(counter.updates(), 1);
   }
-}
\ No newline at end of file
+
+  #[test]
+  fn paused_updater_skips_components() {
+    let counter = HookCounter::default();
+    let scene = counted_scene(&counter);
+    let updater = Updater::new();
+    updater.clone().set_paused(true);
+
+    updater.update(&scene);
+    assert_eq!(counter.updates(), 0);
+
+    updater.set_paused(false);
+    updater.update(&scene);
+    assert_eq!(counter.updates(), 1);
+  }
+
+  #[test]
+  fn paused_updater_runs_always_on_components() {
+    let counter = HookCounter::always_on();
+    let updater = Updater::new();
+    updater.set_paused(true);
+
+    updater.update(&counted_scene(&counter));
+    assert_eq!(counter.updates(), 1);
+  }
+}
@@ -1,3 +1,5 @@
+use std::sync::Arc;
+
 use anyhow::Error;
 use winit::{
   application::ApplicationHandler, dpi::{LogicalSize, Size}, event_loop::EventLoop
@@ -8,15 +10,25 @@ use winit::platform::wayland::EventLoopBuilderExtWayland;
 use winit::platform::windows::EventLoopBuilderExtWindows;
 
 use crate::{
-  config::{app::AppConfig, vulkan::VulkanConfig}, util::signal::Signal, vulkan::Vulkan
+  config::{app::AppConfig, vulkan::VulkanConfig},
+  scene::Scene,
+  util::{frame_limiter::FrameLimiter, mutator::Mutator, signal::Signal},
+  vulkan::Vulkan,
 };
 
+use super::{renderer::Renderer, RendererInfo, Updater};
+
 pub struct Window {
   config: AppConfig,
   vulkan_config: VulkanConfig,
   instance: Option<Vulkan>,
+  scene: Arc<Scene>,
+  updater: Updater,
+  renderer: Renderer,
+  renderer_info: Mutator<Option<RendererInfo>>,
   app_run: Signal,
   window_ready: Signal,
+  frame_limiter: FrameLimiter,
 }
 
 impl Window {
@@ -24,14 +36,22 @@ impl Window {
   pub fn init(
     config: AppConfig,
     vulkan_config: VulkanConfig,
+    scene: Arc<Scene>,
+    updater: Updater,
+    renderer_info: Mutator<Option<RendererInfo>>,
     app_run: Signal,
     window_ready: Signal,
   ) -> Result<(), Error> {
     let event_loop = EventLoop::builder().with_any_thread(true).build()?;
     event_loop.run_app(&mut Window {
+      frame_limiter: FrameLimiter::new(config.max_fps),
       config,
       vulkan_config,
       instance: None,
+      renderer: Renderer::init(scene.clone()),
+      scene,
+      updater,
+      renderer_info,
       app_run,
       window_ready,
     })?;
@@ -57,6 +77,7 @@ impl ApplicationHandler for Window {
       window,
     )
     .unwrap();
+    self.renderer_info.set(Some(v.renderer_info()));
     self.instance = Some(v);
 
     self.window_ready.signal();
@@ -75,7 +96,14 @@ impl ApplicationHandler for Window {
           v.destroy();
         }
       }
+      winit::event::WindowEvent::Resized(size) => {
+        self.renderer.resize(size.width, size.height);
+      }
       winit::event::WindowEvent::RedrawRequested => {
+        self.updater.update(&self.scene);
+        // there is no camera uniform buffer to upload the matrices to yet
+        let _camera_upload = self.renderer.update();
+        self.frame_limiter.wait();
       }
       _ => {}
     }
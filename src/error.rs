@@ -6,4 +6,10 @@ pub enum EngineBuildError {
   StateMissing,
   #[error("Scene is missing")]
   SceneMissing,
-}
\ No newline at end of file
+}
+
+#[derive(Error, Debug)]
+pub enum GameObjectError {
+  #[error("Game object already has a component of type {0}")]
+  DuplicateComponent(&'static str),
+}
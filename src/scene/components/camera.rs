@@ -0,0 +1,271 @@
+use glam as g;
+
+use crate::scene::game_object::GameObjectComponent;
+
+pub enum Projection {
+  Perspective {
+    fov_y: f32,
+    aspect_ratio: f32,
+    near: f32,
+    far: f32,
+  },
+  Orthographic {
+    height: f32,
+    aspect_ratio: f32,
+    near: f32,
+    far: f32,
+  },
+}
+
+pub struct Camera {
+  position: g::Vec3,
+  view_direction: g::Vec3,
+  up: g::Vec3,
+  projection: Projection,
+  reverse_z: bool,
+  view_matrix: g::Mat4,
+  projection_matrix: g::Mat4,
+  changed: bool,
+}
+
+impl Camera {
+  pub fn perspective(fov_y: f32, aspect_ratio: f32, near: f32, far: f32) -> Self {
+    Self::new(Projection::Perspective {
+      fov_y: fov_y.clamp(0.01, std::f32::consts::PI - 0.01),
+      aspect_ratio,
+      near,
+      far,
+    })
+  }
+
+  /// `height` is the vertical extent of the view volume, the width follows from the aspect ratio
+  pub fn orthographic(height: f32, aspect_ratio: f32, near: f32, far: f32) -> Self {
+    Self::new(Projection::Orthographic {
+      height,
+      aspect_ratio,
+      near,
+      far,
+    })
+  }
+
+  fn new(projection: Projection) -> Self {
+    let mut camera = Self {
+      position: g::Vec3::new(0.0, 3.0, -3.0),
+      view_direction: g::Vec3::new(0.0, -1.0, 1.0).normalize(),
+      up: g::Vec3::new(0.0, 1.0, 1.0).normalize(),
+      projection,
+      reverse_z: false,
+      view_matrix: g::Mat4::IDENTITY,
+      projection_matrix: g::Mat4::IDENTITY,
+      changed: true,
+    };
+    camera.update_view_matrix();
+    camera.update_projection_matrix();
+    camera
+  }
+
+  pub fn view_matrix(&self) -> g::Mat4 {
+    self.view_matrix
+  }
+
+  pub fn projection_matrix(&self) -> g::Mat4 {
+    self.projection_matrix
+  }
+
+  /// Whether the view or projection matrix changed since the last `take_changed`
+  pub fn is_changed(&self) -> bool {
+    self.changed
+  }
+
+  /// Returns whether the view or projection matrix changed since the last call
+  pub fn take_changed(&mut self) -> bool {
+    std::mem::take(&mut self.changed)
+  }
+
+  pub fn projection(&self) -> &Projection {
+    &self.projection
+  }
+
+  pub fn position(&self) -> g::Vec3 {
+    self.position
+  }
+
+  pub fn set_position(&mut self, position: g::Vec3) {
+    self.position = position;
+    self.update_view_matrix();
+  }
+
+  pub fn set_view_direction(&mut self, view_direction: g::Vec3) {
+    self.view_direction = view_direction.normalize();
+    self.update_view_matrix();
+  }
+
+  pub fn set_up(&mut self, up: g::Vec3) {
+    self.up = up.normalize();
+    self.update_view_matrix();
+  }
+
+  pub fn set_projection(&mut self, projection: Projection) {
+    self.projection = projection;
+    self.update_projection_matrix();
+  }
+
+  /// Only has an effect on perspective projections
+  pub fn set_fov(&mut self, fov: f32) {
+    if let Projection::Perspective { fov_y, .. } = &mut self.projection {
+      *fov_y = fov.clamp(0.01, std::f32::consts::PI - 0.01);
+    }
+    self.update_projection_matrix();
+  }
+
+  pub fn set_aspect_ratio(&mut self, aspect: f32) {
+    match &mut self.projection {
+      Projection::Perspective { aspect_ratio, .. } => *aspect_ratio = aspect,
+      Projection::Orthographic { aspect_ratio, .. } => *aspect_ratio = aspect,
+    }
+    self.update_projection_matrix();
+  }
+
+  pub fn set_near(&mut self, value: f32) {
+    match &mut self.projection {
+      Projection::Perspective { near, .. } => *near = value,
+      Projection::Orthographic { near, .. } => *near = value,
+    }
+    self.update_projection_matrix();
+  }
+
+  pub fn set_far(&mut self, value: f32) {
+    match &mut self.projection {
+      Projection::Perspective { far, .. } => *far = value,
+      Projection::Orthographic { far, .. } => *far = value,
+    }
+    self.update_projection_matrix();
+  }
+
+  /// Has to match the renderer's reverse z setting
+  pub fn set_reverse_z(&mut self, reverse_z: bool) {
+    self.reverse_z = reverse_z;
+    self.update_projection_matrix();
+  }
+
+  fn update_view_matrix(&mut self) {
+    let view_matrix =
+      g::Mat4::look_at_rh(self.position, self.position + self.view_direction, -self.up);
+    self.changed |= view_matrix != self.view_matrix;
+    self.view_matrix = view_matrix;
+  }
+
+  fn update_projection_matrix(&mut self) {
+    let projection_matrix = match self.projection {
+      Projection::Perspective {
+        fov_y,
+        aspect_ratio,
+        near,
+        far,
+      } => {
+        let (near, far) = self.depth_range(near, far);
+        g::Mat4::perspective_rh(fov_y, aspect_ratio, near, far)
+      }
+      Projection::Orthographic {
+        height,
+        aspect_ratio,
+        near,
+        far,
+      } => {
+        let (near, far) = self.depth_range(near, far);
+        let half_height = height / 2.0;
+        let half_width = half_height * aspect_ratio;
+        g::Mat4::orthographic_rh(
+          -half_width,
+          half_width,
+          -half_height,
+          half_height,
+          near,
+          far,
+        )
+      }
+    };
+    self.changed |= projection_matrix != self.projection_matrix;
+    self.projection_matrix = projection_matrix;
+  }
+
+  /// Swapping the planes maps near to depth 1 and far to depth 0
+  fn depth_range(&self, near: f32, far: f32) -> (f32, f32) {
+    if self.reverse_z {
+      (far, near)
+    } else {
+      (near, far)
+    }
+  }
+}
+
+impl Default for Camera {
+  fn default() -> Self {
+    Self::perspective(std::f32::consts::FRAC_PI_3, 800.0 / 600.0, 0.1, 100.0)
+  }
+}
+
+impl GameObjectComponent for Camera {}
+
+#[cfg(test)]
+mod tests {
+  use glam as g;
+
+  use super::Camera;
+
+  #[test]
+  fn perspective_matches_glam() {
+    let camera = Camera::perspective(1.0, 1.5, 0.1, 50.0);
+    assert_eq!(
+      camera.projection_matrix(),
+      g::Mat4::perspective_rh(1.0, 1.5, 0.1, 50.0)
+    );
+  }
+
+  #[test]
+  fn orthographic_matches_glam() {
+    let camera = Camera::orthographic(10.0, 2.0, 0.1, 50.0);
+    assert_eq!(
+      camera.projection_matrix(),
+      g::Mat4::orthographic_rh(-10.0, 10.0, -5.0, 5.0, 0.1, 50.0)
+    );
+  }
+
+  #[test]
+  fn set_aspect_ratio_rebuilds_projection() {
+    let mut camera = Camera::perspective(1.0, 16.0 / 9.0, 0.1, 50.0);
+    camera.set_aspect_ratio(4.0 / 3.0);
+    assert_eq!(
+      camera.projection_matrix(),
+      g::Mat4::perspective_rh(1.0, 4.0 / 3.0, 0.1, 50.0)
+    );
+  }
+
+  #[test]
+  fn take_changed_resets_after_change() {
+    let mut camera = Camera::default();
+    assert!(camera.is_changed());
+    assert!(camera.take_changed());
+    assert!(!camera.take_changed());
+
+    camera.set_position(camera.position());
+    assert!(!camera.is_changed());
+
+    camera.set_fov(1.0);
+    assert!(camera.is_changed());
+    assert!(camera.take_changed());
+    assert!(!camera.is_changed());
+  }
+
+  #[test]
+  fn reverse_z_maps_near_to_one_and_far_to_zero() {
+    let mut camera = Camera::perspective(1.0, 1.0, 0.1, 50.0);
+    camera.set_reverse_z(true);
+    let depth = |distance: f32| {
+      let clip = camera.projection_matrix() * g::Vec4::new(0.0, 0.0, -distance, 1.0);
+      clip.z / clip.w
+    };
+    assert!((depth(0.1) - 1.0).abs() < 1e-5);
+    assert!(depth(50.0).abs() < 1e-5);
+  }
+}
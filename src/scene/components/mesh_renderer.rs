@@ -1,5 +1,10 @@
 use crate::scene::game_object::GameObjectComponent;
 
+/// Id of `Mesh::cube`, registered by every renderer
+pub const CUBE_MESH: u32 = 0;
+/// Id of `Material::default`, registered by every renderer
+pub const DEFAULT_MATERIAL: u32 = 0;
+
 pub struct MeshRenderer {
   mesh_id: u32,
   material_id: u32,
@@ -1,2 +1,4 @@
+pub mod camera;
 pub mod mesh_renderer;
 pub mod transform;
+pub mod visibility;
@@ -16,6 +16,47 @@ impl Transform {
       scale,
     }
   }
+
+  pub fn model_matrix(&self) -> g::Mat4 {
+    g::Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.position)
+  }
+
+  /// False if the model matrix contains NaN or infinite values or can't be inverted, e.g. because
+  /// of a zero scale
+  pub fn is_valid(&self) -> bool {
+    let model_matrix = self.model_matrix();
+    model_matrix.is_finite() && model_matrix.determinant() != 0.0
+  }
 }
 
-impl GameObjectComponent for Transform {}
\ No newline at end of file
+impl GameObjectComponent for Transform {}
+
+#[cfg(test)]
+mod tests {
+  use glam as g;
+
+  use super::Transform;
+
+  fn transform(position: g::Vec3, scale: g::Vec3) -> Transform {
+    Transform::new(position, g::Quat::IDENTITY, scale)
+  }
+
+  #[test]
+  fn identity_transform_is_valid() {
+    assert!(transform(g::Vec3::ZERO, g::Vec3::ONE).is_valid());
+  }
+
+  #[test]
+  fn zero_scale_is_invalid() {
+    assert!(!transform(g::Vec3::ZERO, g::Vec3::ZERO).is_valid());
+    assert!(!transform(g::Vec3::ZERO, g::Vec3::new(1.0, 0.0, 1.0)).is_valid());
+  }
+
+  #[test]
+  fn non_finite_values_are_invalid() {
+    for value in [f32::NAN, f32::INFINITY, f32::NEG_INFINITY] {
+      assert!(!transform(g::Vec3::splat(value), g::Vec3::ONE).is_valid());
+      assert!(!transform(g::Vec3::ZERO, g::Vec3::splat(value)).is_valid());
+    }
+  }
+}
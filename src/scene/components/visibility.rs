@@ -0,0 +1,27 @@
+use crate::scene::game_object::GameObjectComponent;
+
+pub struct Visibility {
+  visible: bool,
+}
+
+impl Visibility {
+  pub fn new(visible: bool) -> Self {
+    Self { visible }
+  }
+
+  pub fn is_visible(&self) -> bool {
+    self.visible
+  }
+
+  pub fn set_visible(&mut self, visible: bool) {
+    self.visible = visible;
+  }
+}
+
+impl Default for Visibility {
+  fn default() -> Self {
+    Self::new(true)
+  }
+}
+
+impl GameObjectComponent for Visibility {}
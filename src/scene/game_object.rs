@@ -1,8 +1,11 @@
 use std::any::Any;
 
+use crate::error::GameObjectError;
+
 pub struct GameObject {
   id: u32,
   components: Vec<Box<dyn GameObjectComponent>>,
+  despawned: bool,
 }
 
 impl GameObject {
@@ -10,6 +13,7 @@ impl GameObject {
     Self {
       id: rand::random(),
       components: Vec::new(),
+      despawned: false,
     }
   }
 
@@ -17,14 +21,34 @@ impl GameObject {
     self.id
   }
 
-  pub fn add_component<T: GameObjectComponent + 'static>(mut self, component: T) -> Self {
-    self.components.push(Box::new(component));
+  /// True once the game object was removed from its scene, renderers stop tracking it then
+  pub fn is_despawned(&self) -> bool {
+    self.despawned
+  }
+
+  /// Panics if the game object already has a component of the same type
+  pub fn add_component<T: GameObjectComponent + 'static>(self, component: T) -> Self {
     self
+      .try_add_component(component)
+      .unwrap_or_else(|err| panic!("{}", err))
+  }
+
+  pub fn try_add_component<T: GameObjectComponent + 'static>(
+    mut self,
+    component: T,
+  ) -> Result<Self, GameObjectError> {
+    if self.get_component::<T>().is_some() {
+      return Err(GameObjectError::DuplicateComponent(
+        std::any::type_name::<T>(),
+      ));
+    }
+    self.components.push(Box::new(component));
+    Ok(self)
   }
 
   pub fn get_component_mut<T: GameObjectComponent + 'static>(&mut self) -> Option<&mut T> {
-    for component in &mut self.components.iter_mut() {
-      if let Some(component) = (component as &mut dyn Any).downcast_mut::<T>() {
+    for component in self.components.iter_mut() {
+      if let Some(component) = (**component).as_any_mut().downcast_mut::<T>() {
         return Some(component);
       }
     }
@@ -34,7 +58,7 @@ impl GameObject {
 
   pub fn get_component<T: GameObjectComponent + 'static>(&self) -> Option<&T> {
     for component in &self.components {
-      if let Some(component) = (component as &dyn Any).downcast_ref::<T>() {
+      if let Some(component) = (**component).as_any().downcast_ref::<T>() {
         return Some(component);
       }
     }
@@ -42,6 +66,19 @@ impl GameObject {
     None
   }
 
+  pub fn remove_component<T: GameObjectComponent + 'static>(&mut self) -> bool {
+    let index = self
+      .components
+      .iter()
+      .position(|component| (**component).as_any().is::<T>());
+    if let Some(index) = index {
+      self.components.remove(index).on_remove();
+      true
+    } else {
+      false
+    }
+  }
+
   pub fn init(&mut self) {
     for component in self.components.iter_mut() {
       component.init();
@@ -54,11 +91,31 @@ impl GameObject {
     }
   }
 
+  /// Updates only the components that run while the `Updater` is paused
+  pub fn update_while_paused(&mut self) {
+    for component in self.components.iter_mut() {
+      if component.runs_while_paused() {
+        component.update();
+      }
+    }
+  }
+
   pub fn fixed_update(&mut self) {
     for component in self.components.iter_mut() {
       component.fixed_update();
     }
   }
+
+  /// Removes all components, calling `on_remove` once for each of them
+  pub(crate) fn despawn(&mut self) {
+    if self.despawned {
+      return;
+    }
+    for mut component in self.components.drain(..) {
+      component.on_remove();
+    }
+    self.despawned = true;
+  }
 }
 
 impl Default for GameObject {
@@ -67,8 +124,107 @@ impl Default for GameObject {
   }
 }
 
-pub trait GameObjectComponent {
+pub trait AsAny {
+  fn as_any(&self) -> &dyn Any;
+  fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: Any> AsAny for T {
+  fn as_any(&self) -> &dyn Any {
+    self
+  }
+
+  fn as_any_mut(&mut self) -> &mut dyn Any {
+    self
+  }
+}
+
+/// Components are shared with the window thread, which renders them
+pub trait GameObjectComponent: AsAny + Send + Sync {
   fn init(&mut self) {}
   fn update(&mut self) {}
   fn fixed_update(&mut self) {}
+  /// Called once when the component is removed or its game object is removed from the scene
+  fn on_remove(&mut self) {}
+  /// Components that return true keep being updated while the `Updater` is paused, e.g. a camera
+  /// controller in an editor
+  fn runs_while_paused(&self) -> bool {
+    false
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{GameObject, GameObjectComponent};
+  use crate::{error::GameObjectError, scene::testing::HookCounter};
+
+  struct Health(u32);
+
+  impl GameObjectComponent for Health {}
+
+  struct Armor;
+
+  impl GameObjectComponent for Armor {}
+
+  #[test]
+  fn get_component_finds_boxed_component() {
+    let game_object = GameObject::new()
+      .add_component(Armor)
+      .add_component(Health(3));
+    assert_eq!(game_object.get_component::<Health>().unwrap().0, 3);
+    assert!(game_object.get_component::<Armor>().is_some());
+  }
+
+  #[test]
+  fn get_component_mut_changes_component() {
+    let mut game_object = GameObject::new().add_component(Health(3));
+    game_object.get_component_mut::<Health>().unwrap().0 = 5;
+    assert_eq!(game_object.get_component::<Health>().unwrap().0, 5);
+  }
+
+  #[test]
+  fn missing_component_is_none() {
+    let game_object = GameObject::new().add_component(Health(3));
+    assert!(game_object.get_component::<Armor>().is_none());
+  }
+
+  #[test]
+  fn despawn_calls_on_remove_once() {
+    let counter = HookCounter::default();
+    let mut game_object = GameObject::new().add_component(counter.clone());
+
+    game_object.despawn();
+    game_object.despawn();
+
+    assert_eq!(counter.removes(), 1);
+    assert!(game_object.is_despawned());
+    assert!(game_object.get_component::<HookCounter>().is_none());
+  }
+
+  #[test]
+  fn duplicate_component_is_rejected() {
+    let result = GameObject::new()
+      .add_component(Health(3))
+      .try_add_component(Health(4));
+    match result {
+      Err(GameObjectError::DuplicateComponent(type_name)) => {
+        assert_eq!(type_name, std::any::type_name::<Health>());
+        assert!(type_name.ends_with("Health"));
+      }
+      Ok(_) => panic!("duplicate component was added"),
+    }
+  }
+
+  #[test]
+  fn add_component_panics_on_duplicate() {
+    let panic = std::panic::catch_unwind(|| {
+      GameObject::new()
+        .add_component(Health(3))
+        .add_component(Health(4))
+    })
+    .err()
+    .unwrap();
+    let expected = GameObjectError::DuplicateComponent(std::any::type_name::<Health>());
+    assert_eq!(panic.downcast_ref::<String>(), Some(&expected.to_string()));
+  }
 }
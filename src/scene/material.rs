@@ -0,0 +1,46 @@
+use glam as g;
+
+/// Per instance surface parameters, matching the colour, metallic and roughness shader inputs
+#[derive(Debug, Clone, PartialEq)]
+pub struct Material {
+  colour: g::Vec3,
+  metallic: f32,
+  roughness: f32,
+}
+
+impl Material {
+  pub fn new(colour: g::Vec3, metallic: f32, roughness: f32) -> Self {
+    Self {
+      colour,
+      metallic,
+      roughness,
+    }
+  }
+
+  /// The default surface in the given colour
+  pub fn from_colour(colour: g::Vec3) -> Self {
+    Self {
+      colour,
+      ..Self::default()
+    }
+  }
+
+  pub fn colour(&self) -> g::Vec3 {
+    self.colour
+  }
+
+  pub fn metallic(&self) -> f32 {
+    self.metallic
+  }
+
+  pub fn roughness(&self) -> f32 {
+    self.roughness
+  }
+}
+
+/// White dielectric, registered by the renderer under `DEFAULT_MATERIAL`
+impl Default for Material {
+  fn default() -> Self {
+    Self::new(g::Vec3::ONE, 0.0, 0.5)
+  }
+}
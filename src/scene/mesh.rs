@@ -0,0 +1,89 @@
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(C)]
+pub struct Vertex {
+  pub position: [f32; 3],
+  pub normal: [f32; 3],
+  /// Multiplied with the colour of the material
+  pub colour: [f32; 3],
+  pub uv: [f32; 2],
+}
+
+pub struct Mesh {
+  vertices: Vec<Vertex>,
+  indices: Vec<u32>,
+}
+
+impl Mesh {
+  pub fn new(vertices: Vec<Vertex>, indices: Vec<u32>) -> Self {
+    Self { vertices, indices }
+  }
+
+  /// Cube spanning -1 to 1 on every axis, registered by the renderer under `CUBE_MESH`. Every face
+  /// has its own vertices, so it gets its own normal and the full 0 to 1 uv range
+  pub fn cube() -> Self {
+    let lbf = [-1.0, 1.0, -1.0];
+    let lbb = [-1.0, 1.0, 1.0];
+    let ltf = [-1.0, -1.0, -1.0];
+    let ltb = [-1.0, -1.0, 1.0];
+    let rbf = [1.0, 1.0, -1.0];
+    let rbb = [1.0, 1.0, 1.0];
+    let rtf = [1.0, -1.0, -1.0];
+    let rtb = [1.0, -1.0, 1.0];
+
+    let faces = [
+      ([0.0, 1.0, 0.0], [lbf, lbb, rbb, rbf]),  //bottom
+      ([0.0, -1.0, 0.0], [ltf, rtf, rtb, ltb]), //top
+      ([0.0, 0.0, -1.0], [lbf, rbf, rtf, ltf]), //front
+      ([0.0, 0.0, 1.0], [lbb, ltb, rtb, rbb]),  //back
+      ([-1.0, 0.0, 0.0], [lbf, ltf, ltb, lbb]), //left
+      ([1.0, 0.0, 0.0], [rbf, rbb, rtb, rtf]),  //right
+    ];
+    let uvs = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]];
+
+    let mut vertices = Vec::with_capacity(faces.len() * 4);
+    let mut indices = Vec::with_capacity(faces.len() * 6);
+    for (normal, corners) in faces {
+      let first = vertices.len() as u32;
+      vertices.extend(corners.into_iter().zip(uvs).map(|(position, uv)| Vertex {
+        position,
+        normal,
+        colour: [1.0, 1.0, 1.0],
+        uv,
+      }));
+      indices.extend([0, 1, 2, 0, 2, 3].map(|i| first + i));
+    }
+
+    Self::new(vertices, indices)
+  }
+
+  pub fn vertices(&self) -> &[Vertex] {
+    &self.vertices
+  }
+
+  pub fn indices(&self) -> &[u32] {
+    &self.indices
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::Mesh;
+
+  #[test]
+  fn cube_has_separate_vertices_per_face() {
+    let cube = Mesh::cube();
+    assert_eq!(cube.vertices().len(), 24);
+    assert_eq!(cube.indices().len(), 36);
+    assert!(cube.indices().iter().all(|&i| i < 24));
+  }
+
+  #[test]
+  fn cube_faces_cover_whole_uv_range() {
+    let cube = Mesh::cube();
+    for face in cube.vertices().chunks(4) {
+      let uvs: Vec<_> = face.iter().map(|vertex| vertex.uv).collect();
+      assert_eq!(uvs, vec![[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]]);
+      assert!(face.iter().all(|vertex| vertex.normal == face[0].normal));
+    }
+  }
+}
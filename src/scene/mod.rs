@@ -1,13 +1,24 @@
+use components::{
+  mesh_renderer::{MeshRenderer, CUBE_MESH, DEFAULT_MATERIAL},
+  transform::Transform,
+};
 use game_object::GameObject;
+use glam as g;
+use material::Material;
 
 use crate::util::mutator::Mutator;
 
 pub mod game_object;
 pub mod components;
+pub mod material;
+pub mod mesh;
+#[cfg(test)]
+pub(crate) mod testing;
 
 pub struct Scene {
   id: u32,
   game_objects: Vec<Mutator<GameObject>>,
+  materials: Vec<Material>,
 }
 
 impl Scene {
@@ -15,6 +26,7 @@ impl Scene {
     Self {
       id: rand::random(),
       game_objects: Vec::new(),
+      materials: vec![Material::default()],
     }
   }
 
@@ -23,21 +35,87 @@ impl Scene {
     self
   }
 
-  pub fn init(&mut self) {
+  /// Returns the id to reference the material with in a `MeshRenderer`, the renderer registers
+  /// the materials of its scene under the same ids
+  pub fn add_material(&mut self, material: Material) -> u32 {
+    self.materials.push(material);
+    self.materials.len() as u32 - 1
+  }
+
+  /// Cube with the default material in the given colour, white uses `DEFAULT_MATERIAL` itself
+  pub fn spawn_cube(mut self, transform: Transform, colour: g::Vec3) -> Self {
+    let material = if colour == Material::default().colour() {
+      DEFAULT_MATERIAL
+    } else {
+      self.add_material(Material::from_colour(colour))
+    };
+    self.add_game_object(
+      GameObject::new()
+        .add_component(transform)
+        .add_component(MeshRenderer::new(CUBE_MESH, material)),
+    )
+  }
+
+  /// Despawns the game object while the scene is shared, e.g. with a running engine. It stays in
+  /// `game_objects` but is skipped by updates and no longer contained. False if there is no such
+  /// game object
+  pub fn despawn_game_object(&self, id: u32) -> bool {
+    let game_object = self
+      .game_objects
+      .iter()
+      .find(|game_object| game_object.get().id() == id);
+    let Some(game_object) = game_object else {
+      return false;
+    };
+    game_object.get_mut().despawn();
+    true
+  }
+
+  pub fn remove_game_object(&mut self, id: u32) {
+    self.game_objects.retain(|game_object| {
+      let mut game_object = game_object.get_mut();
+      if game_object.id() == id {
+        game_object.despawn();
+        false
+      } else {
+        true
+      }
+    });
+  }
+
+  /// Returns whether a game object with this id is part of the scene, false once it was removed
+  pub fn contains_game_object(&self, id: u32) -> bool {
+    self.game_objects.iter().any(|game_object| {
+      let game_object = game_object.get();
+      game_object.id() == id && !game_object.is_despawned()
+    })
+  }
+
+  pub fn init(&self) {
     for game_object in self.game_objects.iter() {
       game_object.get_mut().init();
     }
   }
 
-  pub fn update(&mut self) {
+  pub fn update(&self) {
     for game_object in self.game_objects.iter() {
       game_object.get_mut().update();
     }
   }
 
+  pub fn update_while_paused(&self) {
+    for game_object in self.game_objects.iter() {
+      game_object.get_mut().update_while_paused();
+    }
+  }
+
   pub fn game_objects(&self) -> &Vec<Mutator<GameObject>> {
     &self.game_objects
   }
+
+  pub fn materials(&self) -> &[Material] {
+    &self.materials
+  }
 }
 
 impl Default for Scene {
@@ -45,3 +123,108 @@ impl Default for Scene {
     Self::new()
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use std::sync::Arc;
+
+  use glam as g;
+
+  use super::{
+    components::{
+      mesh_renderer::{MeshRenderer, CUBE_MESH, DEFAULT_MATERIAL},
+      transform::Transform,
+    },
+    game_object::GameObject,
+    material::Material,
+    testing::HookCounter,
+    Scene,
+  };
+
+  #[test]
+  fn spawn_cube_adds_transform_and_mesh_renderer() {
+    let scene = Scene::new().spawn_cube(
+      Transform::new(g::Vec3::X, g::Quat::IDENTITY, g::Vec3::ONE),
+      g::Vec3::ONE,
+    );
+
+    assert_eq!(scene.game_objects().len(), 1);
+    let game_object = scene.game_objects()[0].get();
+    let transform = game_object.get_component::<Transform>().unwrap();
+    assert_eq!(
+      transform.model_matrix(),
+      g::Mat4::from_translation(g::Vec3::X)
+    );
+    let mesh_renderer = game_object.get_component::<MeshRenderer>().unwrap();
+    assert_eq!(mesh_renderer.mesh_id(), CUBE_MESH);
+    assert_eq!(mesh_renderer.material_id(), DEFAULT_MATERIAL);
+    assert_eq!(scene.materials(), &[Material::default()]);
+  }
+
+  #[test]
+  fn spawn_cube_adds_coloured_material() {
+    let red = g::Vec3::new(1.0, 0.0, 0.0);
+    let scene = Scene::new().spawn_cube(
+      Transform::new(g::Vec3::ZERO, g::Quat::IDENTITY, g::Vec3::ONE),
+      red,
+    );
+
+    let game_object = scene.game_objects()[0].get();
+    let material_id = game_object
+      .get_component::<MeshRenderer>()
+      .unwrap()
+      .material_id();
+    assert_ne!(material_id, DEFAULT_MATERIAL);
+    assert_eq!(
+      scene.materials()[material_id as usize],
+      Material::from_colour(red)
+    );
+  }
+
+  #[test]
+  fn remove_game_object_calls_on_remove_once() {
+    let counter = HookCounter::default();
+    let mut scene = Scene::new().add_game_object(GameObject::new().add_component(counter.clone()));
+    let game_object = scene.game_objects()[0].clone();
+    let id = game_object.get().id();
+
+    scene.remove_game_object(id);
+    scene.remove_game_object(id);
+
+    assert_eq!(counter.removes(), 1);
+    assert!(game_object.get().is_despawned());
+  }
+
+  #[test]
+  fn despawn_on_shared_scene_calls_on_remove_once() {
+    let counter = HookCounter::default();
+    let scene =
+      Arc::new(Scene::new().add_game_object(GameObject::new().add_component(counter.clone())));
+    let id = scene.game_objects()[0].get().id();
+
+    let shared = scene.clone();
+    std::thread::spawn(move || assert!(shared.despawn_game_object(id)))
+      .join()
+      .unwrap();
+    assert!(scene.despawn_game_object(id));
+
+    assert_eq!(counter.removes(), 1);
+    assert!(!scene.contains_game_object(id));
+    assert!(!scene.despawn_game_object(id + 1));
+  }
+
+  #[test]
+  fn contains_game_object_until_removed() {
+    let mut scene = Scene::new()
+      .add_game_object(GameObject::new())
+      .add_game_object(GameObject::new());
+    let removed_id = scene.game_objects()[0].get().id();
+    let kept_id = scene.game_objects()[1].get().id();
+
+    assert!(scene.contains_game_object(removed_id));
+    scene.remove_game_object(removed_id);
+
+    assert!(!scene.contains_game_object(removed_id));
+    assert!(scene.contains_game_object(kept_id));
+  }
+}
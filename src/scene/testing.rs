@@ -0,0 +1,47 @@
+use std::sync::{
+  atomic::{AtomicUsize, Ordering},
+  Arc,
+};
+
+use super::game_object::GameObjectComponent;
+
+/// Component counting how often its hooks ran, clones share the counts so tests can keep one
+/// after adding the other to a game object
+#[derive(Clone, Default)]
+pub(crate) struct HookCounter {
+  updates: Arc<AtomicUsize>,
+  removes: Arc<AtomicUsize>,
+  runs_while_paused: bool,
+}
+
+impl HookCounter {
+  /// Counter that is also updated while the `Updater` is paused
+  pub(crate) fn always_on() -> Self {
+    Self {
+      runs_while_paused: true,
+      ..Default::default()
+    }
+  }
+
+  pub(crate) fn updates(&self) -> usize {
+    self.updates.load(Ordering::SeqCst)
+  }
+
+  pub(crate) fn removes(&self) -> usize {
+    self.removes.load(Ordering::SeqCst)
+  }
+}
+
+impl GameObjectComponent for HookCounter {
+  fn update(&mut self) {
+    self.updates.fetch_add(1, Ordering::SeqCst);
+  }
+
+  fn on_remove(&mut self) {
+    self.removes.fetch_add(1, Ordering::SeqCst);
+  }
+
+  fn runs_while_paused(&self) -> bool {
+    self.runs_while_paused
+  }
+}
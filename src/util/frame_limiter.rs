@@ -0,0 +1,74 @@
+use std::time::{Duration, Instant};
+
+pub struct FrameLimiter {
+  frame_time: Option<Duration>,
+  last_frame: Instant,
+}
+
+impl FrameLimiter {
+  /// `None` or `Some(0)` disables the limit
+  pub fn new(max_fps: Option<u32>) -> Self {
+    Self {
+      frame_time: max_fps
+        .filter(|&max_fps| max_fps > 0)
+        .map(|max_fps| Duration::from_nanos(1_000_000_000 / max_fps as u64)),
+      last_frame: Instant::now(),
+    }
+  }
+
+  /// What is left of the frame budget at `now`, zero once the budget is used up and `None` without
+  /// a limit
+  pub fn remaining(&self, now: Instant) -> Option<Duration> {
+    let deadline = self.last_frame + self.frame_time?;
+    Some(deadline.saturating_duration_since(now))
+  }
+
+  /// Sleeps for what is left of the frame budget since the last call. Deadlines advance by the
+  /// frame time instead of the wake up time so oversleeping doesn't lower the frame rate
+  pub fn wait(&mut self) {
+    let now = Instant::now();
+    let Some(remaining) = self.remaining(now) else {
+      return;
+    };
+
+    std::thread::sleep(remaining);
+    self.last_frame = now + remaining;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::Duration;
+
+  use super::FrameLimiter;
+
+  #[test]
+  fn fast_frame_waits_for_rest_of_budget() {
+    let limiter = FrameLimiter::new(Some(100));
+    let now = limiter.last_frame + Duration::from_millis(2);
+    assert_eq!(limiter.remaining(now), Some(Duration::from_millis(8)));
+  }
+
+  #[test]
+  fn slow_frame_does_not_wait() {
+    let limiter = FrameLimiter::new(Some(100));
+    let now = limiter.last_frame + Duration::from_millis(15);
+    assert_eq!(limiter.remaining(now), Some(Duration::ZERO));
+  }
+
+  #[test]
+  fn unlimited_never_waits() {
+    for max_fps in [None, Some(0)] {
+      let limiter = FrameLimiter::new(max_fps);
+      assert_eq!(limiter.remaining(limiter.last_frame), None);
+    }
+  }
+
+  #[test]
+  fn wait_advances_deadline_by_frame_time() {
+    let mut limiter = FrameLimiter::new(Some(1000));
+    let start = limiter.last_frame;
+    limiter.wait();
+    assert!(limiter.last_frame >= start + Duration::from_millis(1));
+  }
+}
@@ -1,2 +1,3 @@
 pub mod signal;
-pub mod mutator;
\ No newline at end of file
+pub mod mutator;
+pub mod frame_limiter;
\ No newline at end of file
@@ -89,6 +89,26 @@ impl DebugUtils {
   }
 }
 
+pub(crate) struct DebugUtilsDevice {
+  loader: ext::debug_utils::Device,
+}
+
+impl DebugUtilsDevice {
+  pub(crate) fn init(instance: &ash::Instance, device: &ash::Device) -> Self {
+    let loader = ext::debug_utils::Device::new(instance, device);
+    Self { loader }
+  }
+
+  pub(crate) fn set_object_name<T: vk::Handle>(&self, handle: T, name: &str) -> Result<(), Error> {
+    let name = std::ffi::CString::new(name)?;
+    let name_info = vk::DebugUtilsObjectNameInfoEXT::default()
+      .object_handle(handle)
+      .object_name(&name);
+    unsafe { self.loader.set_debug_utils_object_name(&name_info) }?;
+    Ok(())
+  }
+}
+
 #[derive(Debug)]
 pub(crate) struct DebuggerInfo {
   debug_utils: vk::DebugUtilsMessengerCreateInfoEXT<'static>,
@@ -125,3 +145,26 @@ unsafe extern "system" fn vulkan_debug_utils_callback(
 
   vk::FALSE
 }
+
+#[cfg(test)]
+mod tests {
+  use ash::{ext, vk};
+
+  use super::DebugUtilsDevice;
+  use crate::vulkan::headless::Headless;
+
+  #[test]
+  fn set_object_name_with_debug_utils() {
+    let Some(headless) = Headless::init(&[ext::debug_utils::NAME]) else {
+      return;
+    };
+    let debug_utils = DebugUtilsDevice::init(&headless.instance, &headless.device);
+    let semaphore_info = vk::SemaphoreCreateInfo::default();
+    let semaphore = unsafe { headless.device.create_semaphore(&semaphore_info, None) }.unwrap();
+
+    let result = debug_utils.set_object_name(semaphore, "Test Semaphore");
+
+    unsafe { headless.device.destroy_semaphore(semaphore, None) };
+    result.unwrap();
+  }
+}
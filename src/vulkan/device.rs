@@ -1,14 +1,21 @@
 use anyhow::Error;
 use ash::{khr, vk};
 
-use crate::config::vulkan::RendererConfig;
+use crate::config::vulkan::{PipelineType, RendererConfig, VulkanConfig};
 
-use super::{error::QueueFamilyMissingError, surface::Surface};
+use super::{
+  debug::DebugUtilsDevice,
+  error::{DeviceFeatureMissingError, QueueFamilyMissingError},
+  instance::supported_extensions,
+  surface::Surface,
+};
 
 pub(crate) struct Device {
   device: ash::Device,
   queues: Queues,
   queue_families: QueueFamilies,
+  debug_utils: Option<DebugUtilsDevice>,
+  enabled_extensions: Vec<std::ffi::CString>,
 }
 
 impl Device {
@@ -19,15 +26,101 @@ impl Device {
     config: &RendererConfig,
   ) -> Result<Self, Error> {
     let queue_families = QueueFamilies::init(instance, physical_device, surface)?;
-    let (device, queues) = Queues::init(instance, physical_device, &queue_families, config)?;
+    let available_extensions =
+      unsafe { instance.enumerate_device_extension_properties(physical_device) }?;
+    let mut extension_names = vec![khr::swapchain::NAME];
+    extension_names.extend(&config.device_extensions);
+    let optional_extension_names = supported_extensions(
+      &config.optional_device_extensions,
+      &extension_names,
+      &available_extensions,
+    );
+    extension_names.extend(optional_extension_names);
+
+    let (device, queues) = Queues::init(
+      instance,
+      physical_device,
+      &queue_families,
+      &extension_names,
+      config,
+    )?;
+    let debug_utils = if config.debug {
+      Some(DebugUtilsDevice::init(instance, &device))
+    } else {
+      None
+    };
 
     Ok(Self {
       device,
       queues,
       queue_families,
+      debug_utils,
+      enabled_extensions: extension_names
+        .into_iter()
+        .map(|name| name.to_owned())
+        .collect(),
     })
   }
 
+  /// Enables the device features needed by the shader stages and depth bias of the configured
+  /// pipelines
+  pub(crate) fn enable_required_features(
+    instance: &ash::Instance,
+    physical_device: vk::PhysicalDevice,
+    config: &mut VulkanConfig,
+  ) -> Result<(), DeviceFeatureMissingError> {
+    let supported = unsafe { instance.get_physical_device_features(physical_device) };
+    Self::enable_features(&supported, config)
+  }
+
+  fn enable_features(
+    supported: &vk::PhysicalDeviceFeatures,
+    config: &mut VulkanConfig,
+  ) -> Result<(), DeviceFeatureMissingError> {
+    let graphics_pipelines = || {
+      config.shaders.iter().filter_map(|pipeline| match pipeline {
+        PipelineType::Graphics(pipeline) => Some(pipeline),
+        PipelineType::Compute(_) => None,
+      })
+    };
+    let stages = graphics_pipelines()
+      .flat_map(|pipeline| pipeline.shaders.iter())
+      .fold(vk::ShaderStageFlags::empty(), |stages, shader| {
+        stages | shader.type_
+      });
+    let depth_bias_clamp = graphics_pipelines().any(|pipeline| {
+      pipeline
+        .depth_bias
+        .as_ref()
+        .is_some_and(|depth_bias| depth_bias.clamp != 0.0)
+    });
+
+    if stages.contains(vk::ShaderStageFlags::GEOMETRY) {
+      if supported.geometry_shader == vk::FALSE {
+        return Err(DeviceFeatureMissingError::GeometryShader);
+      }
+      config.renderer.device_features.geometry_shader = vk::TRUE;
+    }
+
+    if stages.intersects(
+      vk::ShaderStageFlags::TESSELLATION_CONTROL | vk::ShaderStageFlags::TESSELLATION_EVALUATION,
+    ) {
+      if supported.tessellation_shader == vk::FALSE {
+        return Err(DeviceFeatureMissingError::TessellationShader);
+      }
+      config.renderer.device_features.tessellation_shader = vk::TRUE;
+    }
+
+    if depth_bias_clamp {
+      if supported.depth_bias_clamp == vk::FALSE {
+        return Err(DeviceFeatureMissingError::DepthBiasClamp);
+      }
+      config.renderer.device_features.depth_bias_clamp = vk::TRUE;
+    }
+
+    Ok(())
+  }
+
   pub(crate) fn get_device(&self) -> &ash::Device {
     &self.device
   }
@@ -36,6 +129,19 @@ impl Device {
     &self.queue_families
   }
 
+  /// Required extensions plus the optional ones the physical device supports
+  pub(crate) fn enabled_extensions(&self) -> &[std::ffi::CString] {
+    &self.enabled_extensions
+  }
+
+  /// Labels the object for validation messages and debuggers, does nothing without debug utils
+  pub(crate) fn set_object_name<T: vk::Handle>(&self, handle: T, name: &str) -> Result<(), Error> {
+    if let Some(debug_utils) = &self.debug_utils {
+      debug_utils.set_object_name(handle, name)?;
+    }
+    Ok(())
+  }
+
   pub(crate) fn destroy(&mut self) {
     unsafe {
       self.device.destroy_device(None);
@@ -61,45 +167,53 @@ impl QueueFamilies {
     let queue_family_properties =
       unsafe { instance.get_physical_device_queue_family_properties(physical_device) };
 
-    let mut queue_family_index_graphics = None;
-    let mut queue_family_index_compute = None;
-    let mut queue_family_index_transfer = None;
-    for (i, properties) in queue_family_properties.iter().enumerate() {
-      if properties.queue_count > 0
-        && properties.queue_flags.contains(vk::QueueFlags::GRAPHICS)
-        && surface.get_support(physical_device, i as u32)?
-        && queue_family_index_graphics.is_none()
-      {
-        queue_family_index_graphics = Some(i as u32);
-      }
+    let mut present_support = Vec::with_capacity(queue_family_properties.len());
+    for i in 0..queue_family_properties.len() {
+      present_support.push(surface.get_support(physical_device, i as u32)?);
+    }
 
-      if properties.queue_count > 0
-        && properties.queue_flags.contains(vk::QueueFlags::COMPUTE)
-        && (queue_family_index_compute.is_none()
-          || queue_family_index_graphics == queue_family_index_compute)
-      {
-        queue_family_index_compute = Some(i as u32);
-      }
+    Ok(Self::select(&queue_family_properties, &present_support)?)
+  }
 
-      if properties.queue_count > 0
-        && (properties.queue_flags.contains(vk::QueueFlags::TRANSFER)
-          || properties.queue_flags.contains(vk::QueueFlags::GRAPHICS)
-          || properties.queue_flags.contains(vk::QueueFlags::COMPUTE))
-        && (queue_family_index_transfer.is_none()
-          || queue_family_index_graphics == queue_family_index_transfer
-          || queue_family_index_compute == queue_family_index_transfer)
-      {
-        queue_family_index_transfer = Some(i as u32);
-      }
-    }
+  /// Picks a graphics family that can present and prefers dedicated compute and transfer families,
+  /// falling back to shared ones if the device has none
+  pub(crate) fn select(
+    queue_family_properties: &[vk::QueueFamilyProperties],
+    present_support: &[bool],
+  ) -> Result<Self, QueueFamilyMissingError> {
+    let find = |predicate: &dyn Fn(usize, vk::QueueFlags) -> bool| {
+      queue_family_properties
+        .iter()
+        .enumerate()
+        .find(|(i, properties)| properties.queue_count > 0 && predicate(*i, properties.queue_flags))
+        .map(|(i, _)| i as u32)
+    };
+
+    let graphics = find(&|i, flags| {
+      flags.contains(vk::QueueFlags::GRAPHICS) && present_support.get(i).copied().unwrap_or(false)
+    })
+    .ok_or(QueueFamilyMissingError::Graphics)?;
+
+    let compute = find(&|_, flags| {
+      flags.contains(vk::QueueFlags::COMPUTE) && !flags.contains(vk::QueueFlags::GRAPHICS)
+    })
+    .or_else(|| find(&|_, flags| flags.contains(vk::QueueFlags::COMPUTE)))
+    .ok_or(QueueFamilyMissingError::Compute)?;
+
+    // graphics and compute queues implicitly support transfer operations
+    let transfer = find(&|_, flags| {
+      flags.contains(vk::QueueFlags::TRANSFER)
+        && !flags.intersects(vk::QueueFlags::GRAPHICS | vk::QueueFlags::COMPUTE)
+    })
+    .or_else(|| (compute != graphics).then_some(compute))
+    .unwrap_or(graphics);
 
     Ok(Self {
-      graphics_q_index: queue_family_index_graphics.ok_or(QueueFamilyMissingError::Graphics)?,
-      compute_q_index: queue_family_index_compute.ok_or(QueueFamilyMissingError::Compute)?,
-      transfer_q_index: queue_family_index_transfer.ok_or(QueueFamilyMissingError::Transfer)?,
-      compute_unique: queue_family_index_graphics != queue_family_index_compute,
-      transfer_unique: queue_family_index_graphics != queue_family_index_transfer
-        && queue_family_index_compute != queue_family_index_transfer,
+      graphics_q_index: graphics,
+      compute_q_index: compute,
+      transfer_q_index: transfer,
+      compute_unique: graphics != compute,
+      transfer_unique: graphics != transfer && compute != transfer,
     })
   }
 
@@ -120,6 +234,7 @@ impl Queues {
     instance: &ash::Instance,
     physical_device: vk::PhysicalDevice,
     queue_families: &QueueFamilies,
+    extension_names: &[&std::ffi::CStr],
     config: &RendererConfig,
   ) -> Result<(ash::Device, Self), vk::Result> {
     let queue_priorities = [1.0];
@@ -138,8 +253,8 @@ impl Queues {
         .queue_family_index(queue_families.transfer_q_index)
         .queue_priorities(&queue_priorities));
     }
-    let mut device_extension_name_ptrs = vec![khr::swapchain::NAME.as_ptr()];
-    device_extension_name_ptrs.extend(config.device_extensions.iter().map(|ext| ext.as_ptr()));
+    let device_extension_name_ptrs: Vec<*const i8> =
+      extension_names.iter().map(|ext| ext.as_ptr()).collect();
 
     let features = config.device_features.fill_mode_non_solid(true);
 
@@ -167,3 +282,135 @@ impl Queues {
     ))
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use ash::vk;
+
+  use super::{Device, QueueFamilies};
+  use crate::{
+    config::vulkan::{DepthBias, GraphicsPipelineConfig, ShaderConfig, ShaderType, VulkanConfig},
+    vulkan::error::DeviceFeatureMissingError,
+  };
+
+  fn family(queue_flags: vk::QueueFlags) -> vk::QueueFamilyProperties {
+    vk::QueueFamilyProperties {
+      queue_flags,
+      queue_count: 1,
+      ..Default::default()
+    }
+  }
+
+  fn general_family() -> vk::QueueFamilyProperties {
+    family(vk::QueueFlags::GRAPHICS | vk::QueueFlags::COMPUTE | vk::QueueFlags::TRANSFER)
+  }
+
+  #[test]
+  fn picks_dedicated_transfer_family() {
+    let families = [
+      general_family(),
+      family(vk::QueueFlags::COMPUTE | vk::QueueFlags::TRANSFER),
+      family(vk::QueueFlags::TRANSFER),
+    ];
+    let selected = QueueFamilies::select(&families, &[true, false, false]).unwrap();
+    assert_eq!(selected.graphics_q_index, 0);
+    assert_eq!(selected.compute_q_index, 1);
+    assert_eq!(selected.transfer_q_index, 2);
+    assert!(selected.compute_unique);
+    assert!(selected.transfer_unique);
+  }
+
+  #[test]
+  fn transfer_falls_back_to_dedicated_compute_family() {
+    let families = [
+      general_family(),
+      family(vk::QueueFlags::COMPUTE | vk::QueueFlags::TRANSFER),
+    ];
+    let selected = QueueFamilies::select(&families, &[true, false]).unwrap();
+    assert_eq!(selected.compute_q_index, 1);
+    assert_eq!(selected.transfer_q_index, 1);
+    assert!(selected.compute_unique);
+    assert!(!selected.transfer_unique);
+  }
+
+  #[test]
+  fn single_family_is_shared() {
+    let selected = QueueFamilies::select(&[general_family()], &[true]).unwrap();
+    assert_eq!(selected.graphics_q_index, 0);
+    assert_eq!(selected.compute_q_index, 0);
+    assert_eq!(selected.transfer_q_index, 0);
+    assert!(!selected.compute_unique);
+    assert!(!selected.transfer_unique);
+  }
+
+  fn depth_bias_config(clamp: f32) -> VulkanConfig {
+    VulkanConfig::default().add_graphics_pipeline(
+      GraphicsPipelineConfig::new(
+        "shadow".to_string(),
+        vk::PrimitiveTopology::TRIANGLE_LIST,
+        (800, 600),
+      )
+      .set_depth_bias(DepthBias::new(1.25, 1.75, clamp)),
+    )
+  }
+
+  #[test]
+  fn depth_bias_clamp_enables_feature() {
+    let supported = vk::PhysicalDeviceFeatures::default().depth_bias_clamp(true);
+    let mut config = depth_bias_config(0.5);
+    Device::enable_features(&supported, &mut config).unwrap();
+    assert_eq!(config.renderer.device_features.depth_bias_clamp, vk::TRUE);
+  }
+
+  #[test]
+  fn depth_bias_without_clamp_needs_no_feature() {
+    let mut config = depth_bias_config(0.0);
+    Device::enable_features(&vk::PhysicalDeviceFeatures::default(), &mut config).unwrap();
+    assert_eq!(config.renderer.device_features.depth_bias_clamp, vk::FALSE);
+  }
+
+  #[test]
+  fn unsupported_depth_bias_clamp_is_rejected() {
+    let mut config = depth_bias_config(0.5);
+    let result = Device::enable_features(&vk::PhysicalDeviceFeatures::default(), &mut config);
+    assert!(matches!(
+      result,
+      Err(DeviceFeatureMissingError::DepthBiasClamp)
+    ));
+  }
+
+  fn geometry_config() -> VulkanConfig {
+    VulkanConfig::default().add_graphics_pipeline(
+      GraphicsPipelineConfig::new(
+        "geometry".to_string(),
+        vk::PrimitiveTopology::TRIANGLE_LIST,
+        (800, 600),
+      )
+      .add_shader(ShaderConfig::new(ShaderType::Vertex, vec![]))
+      .add_shader(ShaderConfig::new(ShaderType::Geometry, vec![]))
+      .add_shader(ShaderConfig::new(ShaderType::Fragment, vec![])),
+    )
+  }
+
+  #[test]
+  fn geometry_stage_enables_feature() {
+    let supported = vk::PhysicalDeviceFeatures::default().geometry_shader(true);
+    let mut config = geometry_config();
+    Device::enable_features(&supported, &mut config).unwrap();
+    assert_eq!(config.renderer.device_features.geometry_shader, vk::TRUE);
+    assert_eq!(
+      config.renderer.device_features.tessellation_shader,
+      vk::FALSE
+    );
+  }
+
+  #[test]
+  fn unsupported_geometry_stage_is_rejected() {
+    let mut config = geometry_config();
+    let result = Device::enable_features(&vk::PhysicalDeviceFeatures::default(), &mut config);
+    assert!(matches!(
+      result,
+      Err(DeviceFeatureMissingError::GeometryShader)
+    ));
+  }
+}
@@ -1,3 +1,4 @@
+use ash::vk;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -6,12 +7,60 @@ pub(crate) enum QueueFamilyMissingError {
   Graphics,
   #[error("No compute queue family found")]
   Compute,
-  #[error("No transfer queue family found")]
-  Transfer,
+}
+
+#[derive(Error, Debug)]
+pub(crate) enum DeviceFeatureMissingError {
+  #[error("Geometry shaders are not supported by the device")]
+  GeometryShader,
+  #[error("Tessellation shaders are not supported by the device")]
+  TessellationShader,
+  #[error("Depth bias clamping is not supported by the device")]
+  DepthBiasClamp,
 }
 
 #[derive(Error, Debug)]
 pub(crate) enum RendererInitError {
+  #[error("No Vulkan capable physical device found")]
+  PhysicalDeviceMissing,
   #[error("No surface formats found")]
   FormatMissing,
+  #[error("No surface format can be used as color attachment, available formats: {0:?}")]
+  FormatUnsupported(Vec<vk::Format>),
+}
+
+#[derive(Error, Debug)]
+pub(crate) enum PipelineInitError {
+  #[error("Base pipeline {base} of {pipeline} was not created before it")]
+  BasePipelineMissing { pipeline: String, base: String },
+  #[error("Push constants of {pipeline} use {size} bytes, the device only supports {max}")]
+  PushConstantsTooLarge {
+    pipeline: String,
+    size: u32,
+    max: u32,
+  },
+  #[error("Push constant range of {pipeline} at {offset} with {size} bytes is not 4 byte aligned")]
+  PushConstantMisaligned {
+    pipeline: String,
+    offset: u32,
+    size: u32,
+  },
+  #[error("Depth only pipeline {0} needs depth test and depth write enabled")]
+  DepthOnlyWithoutDepthWrite(String),
+  #[error("Primitive restart of {pipeline} needs a strip or fan topology, not {topology:?}")]
+  PrimitiveRestartUnsupported {
+    pipeline: String,
+    topology: vk::PrimitiveTopology,
+  },
+  #[error("Tessellation shaders of {pipeline} need the PATCH_LIST topology, not {topology:?}")]
+  TessellationWithoutPatchList {
+    pipeline: String,
+    topology: vk::PrimitiveTopology,
+  },
+  #[error("{pipeline} needs 1 to {max} patch control points, not {patch_control_points}")]
+  PatchControlPointsOutOfRange {
+    pipeline: String,
+    patch_control_points: u32,
+    max: u32,
+  },
 }
@@ -5,10 +5,7 @@ use swap_chain::SwapChain;
 
 use crate::config::{app::AppConfig, vulkan::VulkanConfig};
 
-use super::{
-  device::Device, error::RendererInitError, instance::InstanceDevice,
-  surface::Surface,
-};
+use super::{device::Device, instance::InstanceDevice, surface::Surface};
 
 mod pipeline;
 mod swap_chain;
@@ -28,12 +25,6 @@ impl Renderer {
     config: &mut VulkanConfig,
     app_config: &AppConfig
   ) -> Result<Self, Error> {
-    let format = surface
-      .get_formats(instance.get_physical_device())?
-      .first()
-      .ok_or(RendererInitError::FormatMissing)?
-      .format;
-    let render_pass = pipeline::init_render_pass(device.get_device(), format)?;
     let mut swap_chain = SwapChain::init(
       instance.get_instance(),
       instance.get_physical_device(),
@@ -43,14 +34,33 @@ impl Renderer {
       allocator,
       app_config,
     )?;
+    let render_pass = pipeline::init_render_pass(
+      device.get_device(),
+      swap_chain.get_surface_format().format,
+      config.renderer.get_color_attachment_ops(),
+      config.renderer.get_depth_attachment_ops(),
+    )?;
     swap_chain.create_frame_buffers(device.get_device(), render_pass)?;
     let pipeline = PipelineManager::init(
       device.get_device(),
       render_pass,
       &swap_chain.get_extent(),
       &mut config.shaders,
+      config.renderer.reverse_z,
+      instance.get_physical_device_properties(),
+      &config.renderer.get_pipeline_cache_dir(),
     )?;
 
+    for (i, image) in swap_chain.get_images().iter().enumerate() {
+      device.set_object_name(*image, &format!("Swap Chain Image {}", i))?;
+    }
+    device.set_object_name(swap_chain.get_depth_image(), "Depth Image")?;
+    device.set_object_name(render_pass, "Render Pass")?;
+    for pipeline in &pipeline.pipelines {
+      device.set_object_name(pipeline.pipeline, pipeline.get_name())?;
+      device.set_object_name(pipeline.pipeline_layout, pipeline.get_name())?;
+    }
+
     Ok(Self {
       render_pass,
       swap_chain,
@@ -58,15 +68,99 @@ impl Renderer {
     })
   }
 
+  pub(crate) fn get_swap_chain_image_count(&self) -> u32 {
+    self.swap_chain.get_image_count()
+  }
+
   pub(crate) fn destroy(
     &mut self,
     logical_device: &ash::Device,
     allocator: &mut vulkan::Allocator,
   ) {
-    self.pipeline.destroy(logical_device);
+    self.pipeline.destroy();
     self.swap_chain.destroy(logical_device, allocator);
     unsafe {
       logical_device.destroy_render_pass(self.render_pass, None);
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use ash::vk::{self, Handle};
+
+  use super::pipeline::{self, PipelineManager};
+  use crate::{
+    config::vulkan::RendererConfig,
+    vulkan::headless::{Headless, OffscreenTarget},
+  };
+
+  /// The swap chain needs a window and a surface, so the frame is rendered into an offscreen target
+  /// with the render pass and pipelines the renderer creates
+  #[test]
+  fn records_frame_headless() {
+    let Some(headless) = Headless::init(&[]) else {
+      return;
+    };
+    let cache_dir =
+      std::env::temp_dir().join(format!("vulkan_engine_frame_{}", std::process::id()));
+    let config = RendererConfig::default().set_pipeline_cache_dir(&cache_dir);
+    let format = vk::Format::B8G8R8A8_SRGB;
+    let extent = vk::Extent2D::default().width(800).height(600);
+
+    let render_pass = pipeline::init_render_pass(
+      &headless.device,
+      format,
+      config.get_color_attachment_ops(),
+      config.get_depth_attachment_ops(),
+    )
+    .unwrap();
+    let properties = unsafe {
+      headless
+        .instance
+        .get_physical_device_properties(headless.physical_device)
+    };
+    let mut manager = PipelineManager::init(
+      &headless.device,
+      render_pass,
+      &extent,
+      &mut vec![],
+      config.reverse_z,
+      &properties,
+      &config.get_pipeline_cache_dir(),
+    )
+    .unwrap();
+    let target = OffscreenTarget::init(&headless, render_pass, format, extent);
+
+    headless.submit_and_wait(|command_buffer| unsafe {
+      let clear_values = [
+        vk::ClearValue {
+          color: vk::ClearColorValue {
+            float32: [0.0, 0.0, 0.0, 1.0],
+          },
+        },
+        vk::ClearValue {
+          depth_stencil: vk::ClearDepthStencilValue::default().depth(1.0),
+        },
+      ];
+      let render_pass_info = vk::RenderPassBeginInfo::default()
+        .render_pass(render_pass)
+        .framebuffer(target.framebuffer)
+        .render_area(extent.into())
+        .clear_values(&clear_values);
+      headless.device.cmd_begin_render_pass(
+        command_buffer,
+        &render_pass_info,
+        vk::SubpassContents::INLINE,
+      );
+      headless.device.cmd_end_render_pass(command_buffer);
+    });
+
+    assert_eq!(manager.pipelines[0].get_name(), "default");
+    assert_ne!(manager.pipelines[0].pipeline, vk::Pipeline::null());
+    manager.destroy();
+    target.destroy(&headless.device);
+    unsafe { headless.device.destroy_render_pass(render_pass, None) };
+    let _ = std::fs::remove_dir_all(&cache_dir);
+  }
+}
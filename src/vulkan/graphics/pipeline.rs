@@ -1,34 +1,24 @@
+use std::{
+  collections::HashSet,
+  path::{Path, PathBuf},
+};
+
+use anyhow::Error;
 use ash::vk;
 
 use crate::config::vulkan::{
-  ComputePipelineConfig, Descriptor, DescriptorSet, GraphicsPipelineConfig, PipelineType,
-  ShaderConfig, ShaderInputBindings, ShaderInputVariable, ShaderType,
+  AttachmentOps, ComputePipelineConfig, Descriptor, DescriptorSet, GraphicsPipelineConfig,
+  PipelineType, PushConstant, ShaderConfig, ShaderInputBindings, ShaderInputVariable, ShaderType,
 };
+use crate::vulkan::{error::PipelineInitError, DestroyOnce};
 
 pub(crate) fn init_render_pass(
   logical_device: &ash::Device,
   format: vk::Format,
+  color_ops: AttachmentOps,
+  depth_ops: AttachmentOps,
 ) -> Result<vk::RenderPass, vk::Result> {
-  let attachment = [
-    vk::AttachmentDescription::default()
-      .format(format)
-      .samples(vk::SampleCountFlags::TYPE_1)
-      .load_op(vk::AttachmentLoadOp::CLEAR)
-      .store_op(vk::AttachmentStoreOp::STORE)
-      .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
-      .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
-      .initial_layout(vk::ImageLayout::UNDEFINED)
-      .final_layout(vk::ImageLayout::PRESENT_SRC_KHR),
-    vk::AttachmentDescription::default()
-      .format(vk::Format::D32_SFLOAT)
-      .samples(vk::SampleCountFlags::TYPE_1)
-      .load_op(vk::AttachmentLoadOp::CLEAR)
-      .store_op(vk::AttachmentStoreOp::DONT_CARE)
-      .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
-      .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
-      .initial_layout(vk::ImageLayout::UNDEFINED)
-      .final_layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL),
-  ];
+  let attachment = attachment_descriptions(format, color_ops, depth_ops);
 
   let color_attachment_ref = [vk::AttachmentReference::default()
     .attachment(0)
@@ -58,8 +48,52 @@ pub(crate) fn init_render_pass(
   unsafe { logical_device.create_render_pass(&render_pass_create_info, None) }
 }
 
+/// Color attachment 0 for the swap chain image and depth attachment 1
+fn attachment_descriptions(
+  format: vk::Format,
+  color_ops: AttachmentOps,
+  depth_ops: AttachmentOps,
+) -> [vk::AttachmentDescription; 2] {
+  // loaded attachments have to keep the layout the previous pass left them in, images that were
+  // never rendered to are still UNDEFINED then, see `RendererConfig::set_color_attachment_ops`
+  let initial_layout = |ops: AttachmentOps, loaded_layout| {
+    if ops.load_op == vk::AttachmentLoadOp::LOAD {
+      loaded_layout
+    } else {
+      vk::ImageLayout::UNDEFINED
+    }
+  };
+
+  [
+    vk::AttachmentDescription::default()
+      .format(format)
+      .samples(vk::SampleCountFlags::TYPE_1)
+      .load_op(color_ops.load_op)
+      .store_op(color_ops.store_op)
+      .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+      .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+      .initial_layout(initial_layout(color_ops, vk::ImageLayout::PRESENT_SRC_KHR))
+      .final_layout(vk::ImageLayout::PRESENT_SRC_KHR),
+    vk::AttachmentDescription::default()
+      .format(vk::Format::D32_SFLOAT)
+      .samples(vk::SampleCountFlags::TYPE_1)
+      .load_op(depth_ops.load_op)
+      .store_op(depth_ops.store_op)
+      .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+      .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+      .initial_layout(initial_layout(
+        depth_ops,
+        vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+      ))
+      .final_layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL),
+  ]
+}
+
 pub(crate) struct PipelineManager {
   pub(crate) pipelines: Vec<Pipeline>,
+  cache_dir: PathBuf,
+  logical_device: ash::Device,
+  destroyed: DestroyOnce,
 }
 
 impl PipelineManager {
@@ -68,44 +102,108 @@ impl PipelineManager {
     render_pass: vk::RenderPass,
     swap_chain_extent: &vk::Extent2D,
     pipelines: &mut Vec<PipelineType>,
-  ) -> Result<Self, vk::Result> {
+    reverse_z: bool,
+    device_properties: &vk::PhysicalDeviceProperties,
+    cache_dir: &Path,
+  ) -> Result<Self, Error> {
+    let cache_header = PipelineCacheHeader::new(device_properties);
     pipelines.push(PipelineType::Graphics(Pipeline::default_shader(
       swap_chain_extent,
     )));
 
-    let mut vk_pipelines = vec![];
-    for pipeline in pipelines {
-      match pipeline {
-        PipelineType::Graphics(config) => {
-          vk_pipelines.push(Pipeline::init_graphics_pipeline(
-            logical_device,
-            render_pass,
-            config,
-          )?);
-        }
-        PipelineType::Compute(_) => {}
-      }
+    let graphics_configs: Vec<_> = pipelines
+      .iter()
+      .filter_map(|pipeline| match pipeline {
+        PipelineType::Graphics(config) => Some(config),
+        PipelineType::Compute(_) => None,
+      })
+      .collect();
+    let base_names: HashSet<_> = graphics_configs
+      .iter()
+      .filter_map(|config| config.base_pipeline.clone())
+      .collect();
+    let base_indices = Self::base_pipeline_indices(&graphics_configs)?;
+
+    let mut vk_pipelines: Vec<Pipeline> = vec![];
+    for (config, base_index) in graphics_configs.into_iter().zip(base_indices) {
+      Pipeline::validate_graphics_pipeline(config, &device_properties.limits)?;
+
+      let base_pipeline = base_index.map(|index| vk_pipelines[index].pipeline);
+      vk_pipelines.push(Pipeline::init_graphics_pipeline(
+        logical_device,
+        render_pass,
+        config,
+        reverse_z,
+        cache_header,
+        cache_dir,
+        base_names.contains(&config.name),
+        base_pipeline,
+      )?);
     }
 
     Ok(Self {
       pipelines: vk_pipelines,
+      cache_dir: cache_dir.to_path_buf(),
+      logical_device: logical_device.clone(),
+      destroyed: DestroyOnce::default(),
     })
   }
 
-  pub(crate) fn destroy(&self, logical_device: &ash::Device) {
-    std::fs::create_dir_all("cache").unwrap();
+  /// Index of the base pipeline of every config, bases have to come before their derivatives
+  fn base_pipeline_indices(
+    configs: &[&GraphicsPipelineConfig],
+  ) -> Result<Vec<Option<usize>>, PipelineInitError> {
+    configs
+      .iter()
+      .enumerate()
+      .map(|(i, config)| {
+        let Some(base) = &config.base_pipeline else {
+          return Ok(None);
+        };
+        configs[..i]
+          .iter()
+          .position(|previous| &previous.name == base)
+          .map(Some)
+          .ok_or_else(|| PipelineInitError::BasePipelineMissing {
+            pipeline: config.name.clone(),
+            base: base.clone(),
+          })
+      })
+      .collect()
+  }
+
+  /// Destroys all pipelines and saves their caches, also done on drop if not called before
+  pub(crate) fn destroy(&mut self) {
+    if !self.destroyed.first() {
+      return;
+    }
+
+    if let Err(err) = std::fs::create_dir_all(&self.cache_dir) {
+      println!(
+        "Failed to create pipeline cache directory {:?}: {err}",
+        self.cache_dir
+      );
+    }
     for pipeline in &self.pipelines {
-      pipeline.destroy(logical_device);
+      pipeline.destroy(&self.logical_device);
     }
   }
 }
 
+impl Drop for PipelineManager {
+  fn drop(&mut self) {
+    self.destroy();
+  }
+}
+
 pub(crate) struct Pipeline {
   name: String,
   pub pipeline: vk::Pipeline,
   pub pipeline_layout: vk::PipelineLayout,
   pub descriptor_set_layouts: Vec<vk::DescriptorSetLayout>,
   cache: vk::PipelineCache,
+  cache_header: PipelineCacheHeader,
+  cache_path: PathBuf,
 }
 
 impl Pipeline {
@@ -126,7 +224,9 @@ impl Pipeline {
     .add_input(
       ShaderInputBindings::new(vk::VertexInputRate::VERTEX)
         .add_variable(ShaderInputVariable::Vec3)
-        .add_variable(ShaderInputVariable::Vec3),
+        .add_variable(ShaderInputVariable::Vec3)
+        .add_variable(ShaderInputVariable::Vec3)
+        .add_variable(ShaderInputVariable::Vec2),
     )
     .add_input(
       ShaderInputBindings::new(vk::VertexInputRate::INSTANCE)
@@ -148,24 +248,56 @@ impl Pipeline {
     )))
   }
 
+  pub(crate) fn get_name(&self) -> &str {
+    &self.name
+  }
+
+  /// Records the draw for a `GraphicsPipelineConfig::fullscreen_triangle` pipeline, no vertex
+  /// buffers have to be bound
+  #[allow(dead_code)]
+  pub(crate) fn record_fullscreen(
+    &self,
+    logical_device: &ash::Device,
+    command_buffer: vk::CommandBuffer,
+  ) {
+    unsafe {
+      logical_device.cmd_bind_pipeline(
+        command_buffer,
+        vk::PipelineBindPoint::GRAPHICS,
+        self.pipeline,
+      );
+      logical_device.cmd_draw(command_buffer, 3, 1, 0, 0);
+    }
+  }
+
   pub(crate) fn init_compute_pipeline(
     logical_device: &ash::Device,
     pipeline: &ComputePipelineConfig,
+    cache_header: PipelineCacheHeader,
+    cache_dir: &Path,
   ) -> Result<Self, vk::Result> {
     let main_function_name = std::ffi::CString::new("main").unwrap();
 
     let shader_create_info = vk::ShaderModuleCreateInfo::default().code(&pipeline.shader.code);
     let shader_module = unsafe { logical_device.create_shader_module(&shader_create_info, None) }?;
 
+    let (specialization_entries, specialization_data) = pipeline.shader.specialization_data();
+    let specialization_info = vk::SpecializationInfo::default()
+      .map_entries(&specialization_entries)
+      .data(&specialization_data);
+
     let shader_stage_create_info = vk::PipelineShaderStageCreateInfo::default()
       .stage(pipeline.shader.type_)
       .module(shader_module)
-      .name(&main_function_name);
+      .name(&main_function_name)
+      .specialization_info(&specialization_info);
 
     let descriptor_layouts = Self::get_descriptor_set_layouts(&pipeline.descriptor_sets, logical_device)?;
 
-    let pipeline_layout_create_info =
-      vk::PipelineLayoutCreateInfo::default().set_layouts(&descriptor_layouts);
+    let push_constant_ranges = Self::get_push_constant_ranges(&pipeline.push_constants);
+    let pipeline_layout_create_info = vk::PipelineLayoutCreateInfo::default()
+      .set_layouts(&descriptor_layouts)
+      .push_constant_ranges(&push_constant_ranges);
     let pipeline_layout =
       unsafe { logical_device.create_pipeline_layout(&pipeline_layout_create_info, None) }?;
 
@@ -173,7 +305,8 @@ impl Pipeline {
       .stage(shader_stage_create_info)
       .layout(pipeline_layout);
 
-    let pipeline_cache = Self::create_shader_cache(logical_device, &pipeline.name)?;
+    let cache_path = Self::cache_path(cache_dir, &pipeline.name);
+    let pipeline_cache = Self::create_shader_cache(logical_device, &cache_path, &cache_header)?;
 
     let vk_pipelines = unsafe {
       logical_device
@@ -191,6 +324,8 @@ impl Pipeline {
       pipeline_layout,
       descriptor_set_layouts: descriptor_layouts,
       cache: pipeline_cache,
+      cache_header,
+      cache_path,
     })
   }
 
@@ -198,6 +333,11 @@ impl Pipeline {
     logical_device: &ash::Device,
     render_pass: vk::RenderPass,
     pipeline: &GraphicsPipelineConfig,
+    reverse_z: bool,
+    cache_header: PipelineCacheHeader,
+    cache_dir: &Path,
+    allow_derivatives: bool,
+    base_pipeline: Option<vk::Pipeline>,
   ) -> Result<Self, vk::Result> {
     let main_function_name = std::ffi::CString::new("main").unwrap();
 
@@ -209,19 +349,145 @@ impl Pipeline {
       shader_modules.push((shader_module, shader.type_));
     }
 
+    let specialization_data: Vec<_> = pipeline
+      .shaders
+      .iter()
+      .map(|shader| shader.specialization_data())
+      .collect();
+    let specialization_infos: Vec<_> = specialization_data
+      .iter()
+      .map(|(entries, data)| {
+        vk::SpecializationInfo::default()
+          .map_entries(entries)
+          .data(data)
+      })
+      .collect();
+
     let mut shader_stages = vec![];
-    for shader in &shader_modules {
+    for (shader, specialization_info) in shader_modules.iter().zip(&specialization_infos) {
       let shader_stage_create_info = vk::PipelineShaderStageCreateInfo::default()
         .stage(shader.1)
         .module(shader.0)
-        .name(&main_function_name);
+        .name(&main_function_name)
+        .specialization_info(specialization_info);
       shader_stages.push(shader_stage_create_info);
     }
 
+    let (vertex_attrib_descs, vertex_binding_descs) =
+      Self::vertex_input_descriptions(&pipeline.input);
+
+    let vertex_input_info = vk::PipelineVertexInputStateCreateInfo::default()
+      .vertex_binding_descriptions(&vertex_binding_descs)
+      .vertex_attribute_descriptions(&vertex_attrib_descs);
+    let input_assembly_info = Self::input_assembly_state(pipeline);
+
+    let tessellation_info = vk::PipelineTessellationStateCreateInfo::default()
+      .patch_control_points(pipeline.patch_control_points);
+
+    let viewport = [vk::Viewport::default()
+      .x(0.0)
+      .y(0.0)
+      .width(pipeline.viewport_size.0 as f32)
+      .height(pipeline.viewport_size.1 as f32)
+      .min_depth(0.0)
+      .max_depth(1.0)];
+    let scissor = [vk::Rect2D::default()
+      .offset(vk::Offset2D::default())
+      .extent(vk::Extent2D {
+        width: pipeline.viewport_size.0,
+        height: pipeline.viewport_size.1,
+      })];
+
+    let viewport_info = vk::PipelineViewportStateCreateInfo::default()
+      .viewports(&viewport)
+      .scissors(&scissor);
+
+    let rasterizer_info = Self::rasterization_state(pipeline);
+
+    let multisample_info = vk::PipelineMultisampleStateCreateInfo::default()
+      .rasterization_samples(vk::SampleCountFlags::TYPE_1);
+
+    let color_blend_attachment = [Self::color_blend_attachment(pipeline)];
+    let color_blend_info =
+      vk::PipelineColorBlendStateCreateInfo::default().attachments(&color_blend_attachment);
+
+    let descriptor_layouts = Self::get_descriptor_set_layouts(&pipeline.descriptor_sets, logical_device)?;
+
+    let push_constant_ranges = Self::get_push_constant_ranges(&pipeline.push_constants);
+    let pipeline_layout_create_info = vk::PipelineLayoutCreateInfo::default()
+      .set_layouts(&descriptor_layouts)
+      .push_constant_ranges(&push_constant_ranges);
+    let pipeline_layout =
+      unsafe { logical_device.create_pipeline_layout(&pipeline_layout_create_info, None) }?;
+
+    let depth_stencil_info = Self::depth_stencil_state(pipeline, reverse_z);
+
+    let mut pipeline_create_info = vk::GraphicsPipelineCreateInfo::default()
+      .stages(&shader_stages)
+      .vertex_input_state(&vertex_input_info)
+      .input_assembly_state(&input_assembly_info)
+      .viewport_state(&viewport_info)
+      .rasterization_state(&rasterizer_info)
+      .multisample_state(&multisample_info)
+      .depth_stencil_state(&depth_stencil_info)
+      .color_blend_state(&color_blend_info)
+      .layout(pipeline_layout)
+      .render_pass(render_pass)
+      .subpass(0);
+    if pipeline.has_tessellation() {
+      pipeline_create_info = pipeline_create_info.tessellation_state(&tessellation_info);
+    }
+
+    let mut flags = vk::PipelineCreateFlags::empty();
+    if allow_derivatives {
+      flags |= vk::PipelineCreateFlags::ALLOW_DERIVATIVES;
+    }
+    if let Some(base_pipeline) = base_pipeline {
+      flags |= vk::PipelineCreateFlags::DERIVATIVE;
+      pipeline_create_info = pipeline_create_info
+        .base_pipeline_handle(base_pipeline)
+        .base_pipeline_index(-1);
+    }
+    pipeline_create_info = pipeline_create_info.flags(flags);
+
+    let cache_path = Self::cache_path(cache_dir, &pipeline.name);
+    let pipeline_cache = Self::create_shader_cache(logical_device, &cache_path, &cache_header)?;
+
+    let vk_pipelines = unsafe {
+      logical_device
+        .create_graphics_pipelines(pipeline_cache, &[pipeline_create_info], None)
+        .expect("Unable to create graphics pipeline")
+    }[0];
+
+    for module in shader_modules {
+      unsafe {
+        logical_device.destroy_shader_module(module.0, None);
+      }
+    }
+
+    Ok(Self {
+      name: pipeline.name.clone(),
+      pipeline: vk_pipelines,
+      pipeline_layout,
+      descriptor_set_layouts: descriptor_layouts,
+      cache: pipeline_cache,
+      cache_header,
+      cache_path,
+    })
+  }
+
+  /// Attributes get consecutive locations across all bindings, every binding is tightly packed in
+  /// the order its variables were added
+  fn vertex_input_descriptions(
+    inputs: &[ShaderInputBindings],
+  ) -> (
+    Vec<vk::VertexInputAttributeDescription>,
+    Vec<vk::VertexInputBindingDescription>,
+  ) {
     let mut vertex_attrib_descs = vec![];
     let mut vertex_binding_descs = vec![];
 
-    for (i, input) in pipeline.input.iter().enumerate() {
+    for (i, input) in inputs.iter().enumerate() {
       let mut current_offset = 0;
 
       for variable in &input.variables {
@@ -285,102 +551,73 @@ impl Pipeline {
       );
     }
 
-    let vertex_input_info = vk::PipelineVertexInputStateCreateInfo::default()
-      .vertex_binding_descriptions(&vertex_binding_descs)
-      .vertex_attribute_descriptions(&vertex_attrib_descs);
-    let input_assembly_info =
-      vk::PipelineInputAssemblyStateCreateInfo::default().topology(pipeline.topology);
-
-    let viewport = [vk::Viewport::default()
-      .x(0.0)
-      .y(0.0)
-      .width(pipeline.viewport_size.0 as f32)
-      .height(pipeline.viewport_size.1 as f32)
-      .min_depth(0.0)
-      .max_depth(1.0)];
-    let scissor = [vk::Rect2D::default()
-      .offset(vk::Offset2D::default())
-      .extent(vk::Extent2D {
-        width: pipeline.viewport_size.0,
-        height: pipeline.viewport_size.1,
-      })];
+    (vertex_attrib_descs, vertex_binding_descs)
+  }
 
-    let viewport_info = vk::PipelineViewportStateCreateInfo::default()
-      .viewports(&viewport)
-      .scissors(&scissor);
+  fn input_assembly_state(
+    pipeline: &GraphicsPipelineConfig,
+  ) -> vk::PipelineInputAssemblyStateCreateInfo<'static> {
+    vk::PipelineInputAssemblyStateCreateInfo::default()
+      .topology(pipeline.topology)
+      .primitive_restart_enable(pipeline.primitive_restart)
+  }
 
+  /// A non zero depth bias clamp relies on `Device::enable_required_features` enabling the
+  /// depthBiasClamp feature
+  fn rasterization_state(
+    pipeline: &GraphicsPipelineConfig,
+  ) -> vk::PipelineRasterizationStateCreateInfo<'static> {
     let rasterizer_info = vk::PipelineRasterizationStateCreateInfo::default()
       .line_width(1.0)
       .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
       .cull_mode(vk::CullModeFlags::BACK)
       .polygon_mode(vk::PolygonMode::FILL);
+    match &pipeline.depth_bias {
+      Some(depth_bias) => rasterizer_info
+        .depth_bias_enable(true)
+        .depth_bias_constant_factor(depth_bias.constant_factor)
+        .depth_bias_slope_factor(depth_bias.slope_factor)
+        .depth_bias_clamp(depth_bias.clamp),
+      None => rasterizer_info,
+    }
+  }
 
-    let multisample_info = vk::PipelineMultisampleStateCreateInfo::default()
-      .rasterization_samples(vk::SampleCountFlags::TYPE_1);
-
-    let color_blend_attachment = [vk::PipelineColorBlendAttachmentState::default()
-      .color_write_mask(
-        vk::ColorComponentFlags::R
-          | vk::ColorComponentFlags::G
-          | vk::ColorComponentFlags::B
-          | vk::ColorComponentFlags::A,
-      )
+  /// With reverse z the near plane is at depth 1, so closer fragments have the greater depth
+  fn color_blend_attachment(
+    pipeline: &GraphicsPipelineConfig,
+  ) -> vk::PipelineColorBlendAttachmentState {
+    // without a fragment shader the color attachment content would be undefined
+    let color_write_mask = if pipeline.is_depth_only() {
+      vk::ColorComponentFlags::empty()
+    } else {
+      vk::ColorComponentFlags::R
+        | vk::ColorComponentFlags::G
+        | vk::ColorComponentFlags::B
+        | vk::ColorComponentFlags::A
+    };
+    vk::PipelineColorBlendAttachmentState::default()
+      .color_write_mask(color_write_mask)
       .blend_enable(false)
       .src_color_blend_factor(vk::BlendFactor::SRC_ALPHA)
       .dst_color_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
       .color_blend_op(vk::BlendOp::ADD)
       .src_alpha_blend_factor(vk::BlendFactor::SRC_ALPHA)
       .dst_alpha_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
-      .alpha_blend_op(vk::BlendOp::ADD)];
-    let color_blend_info =
-      vk::PipelineColorBlendStateCreateInfo::default().attachments(&color_blend_attachment);
-
-    let descriptor_layouts = Self::get_descriptor_set_layouts(&pipeline.descriptor_sets, logical_device)?;
-
-    let pipeline_layout_create_info =
-      vk::PipelineLayoutCreateInfo::default().set_layouts(&descriptor_layouts);
-    let pipeline_layout =
-      unsafe { logical_device.create_pipeline_layout(&pipeline_layout_create_info, None) }?;
-
-    let depth_stencil_info = vk::PipelineDepthStencilStateCreateInfo::default()
-      .depth_test_enable(true)
-      .depth_write_enable(true)
-      .depth_compare_op(vk::CompareOp::LESS_OR_EQUAL);
-
-    let pipeline_create_info = vk::GraphicsPipelineCreateInfo::default()
-      .stages(&shader_stages)
-      .vertex_input_state(&vertex_input_info)
-      .input_assembly_state(&input_assembly_info)
-      .viewport_state(&viewport_info)
-      .rasterization_state(&rasterizer_info)
-      .multisample_state(&multisample_info)
-      .depth_stencil_state(&depth_stencil_info)
-      .color_blend_state(&color_blend_info)
-      .layout(pipeline_layout)
-      .render_pass(render_pass)
-      .subpass(0);
-
-    let pipeline_cache = Self::create_shader_cache(logical_device, &pipeline.name)?;
-
-    let vk_pipelines = unsafe {
-      logical_device
-        .create_graphics_pipelines(pipeline_cache, &[pipeline_create_info], None)
-        .expect("Unable to create graphics pipeline")
-    }[0];
-
-    for module in shader_modules {
-      unsafe {
-        logical_device.destroy_shader_module(module.0, None);
-      }
-    }
+      .alpha_blend_op(vk::BlendOp::ADD)
+  }
 
-    Ok(Self {
-      name: pipeline.name.clone(),
-      pipeline: vk_pipelines,
-      pipeline_layout,
-      descriptor_set_layouts: descriptor_layouts,
-      cache: pipeline_cache,
-    })
+  fn depth_stencil_state(
+    pipeline: &GraphicsPipelineConfig,
+    reverse_z: bool,
+  ) -> vk::PipelineDepthStencilStateCreateInfo<'static> {
+    vk::PipelineDepthStencilStateCreateInfo::default()
+      .depth_test_enable(pipeline.depth_test)
+      .depth_write_enable(pipeline.depth_write)
+      .depth_compare_op(if reverse_z {
+        vk::CompareOp::GREATER_OR_EQUAL
+      } else {
+        vk::CompareOp::LESS_OR_EQUAL
+      })
   }
 
   fn get_descriptor_set_layouts(
@@ -389,16 +626,8 @@ impl Pipeline {
   ) -> Result<Vec<vk::DescriptorSetLayout>, vk::Result> {
     let mut descriptor_layouts = vec![];
     for descriptor_set in descriptor_sets {
-      let mut descriptor_set_layout_binding_descs = vec![];
-      for (i, descriptor) in descriptor_set.descriptors.iter().enumerate() {
-        descriptor_set_layout_binding_descs.push(
-          vk::DescriptorSetLayoutBinding::default()
-            .binding(i as u32)
-            .descriptor_type(descriptor.type_)
-            .descriptor_count(1)
-            .stage_flags(descriptor.stage),
-        );
-      }
+      let descriptor_set_layout_binding_descs =
+        Self::descriptor_set_layout_bindings(descriptor_set);
 
       let descriptor_set_layout_create_info =
         vk::DescriptorSetLayoutCreateInfo::default().bindings(&descriptor_set_layout_binding_descs);
@@ -411,11 +640,133 @@ impl Pipeline {
     Ok(descriptor_layouts)
   }
 
-  fn create_shader_cache(logical_device: &ash::Device, name: &str) -> Result<vk::PipelineCache, vk::Result> {
-    let initial_data = if let Ok(data) = std::fs::read(format!("cache/{}.bin", name)) {
-      data
-    } else {
-      vec![]
+  /// One binding per descriptor, numbered in the order they were added to the set
+  fn descriptor_set_layout_bindings(
+    descriptor_set: &DescriptorSet,
+  ) -> Vec<vk::DescriptorSetLayoutBinding<'static>> {
+    descriptor_set
+      .descriptors
+      .iter()
+      .enumerate()
+      .map(|(i, descriptor)| {
+        vk::DescriptorSetLayoutBinding::default()
+          .binding(i as u32)
+          .descriptor_type(descriptor.type_)
+          .descriptor_count(descriptor.descriptor_count)
+          .stage_flags(descriptor.stage)
+      })
+      .collect()
+  }
+
+  fn get_push_constant_ranges(push_constants: &[PushConstant]) -> Vec<vk::PushConstantRange> {
+    push_constants
+      .iter()
+      .map(|push_constant| {
+        vk::PushConstantRange::default()
+          .stage_flags(push_constant.stage)
+          .offset(push_constant.offset)
+          .size(push_constant.size)
+      })
+      .collect()
+  }
+
+  /// Catches invalid configurations that Vulkan would only report through validation layers
+  pub(crate) fn validate_graphics_pipeline(
+    config: &GraphicsPipelineConfig,
+    limits: &vk::PhysicalDeviceLimits,
+  ) -> Result<(), PipelineInitError> {
+    Self::validate_push_constants(
+      &config.name,
+      &config.push_constants,
+      limits.max_push_constants_size,
+    )?;
+
+    if config.is_depth_only() && !(config.depth_test && config.depth_write) {
+      return Err(PipelineInitError::DepthOnlyWithoutDepthWrite(
+        config.name.clone(),
+      ));
+    }
+
+    let list_topology = matches!(
+      config.topology,
+      vk::PrimitiveTopology::POINT_LIST
+        | vk::PrimitiveTopology::LINE_LIST
+        | vk::PrimitiveTopology::TRIANGLE_LIST
+        | vk::PrimitiveTopology::LINE_LIST_WITH_ADJACENCY
+        | vk::PrimitiveTopology::TRIANGLE_LIST_WITH_ADJACENCY
+        | vk::PrimitiveTopology::PATCH_LIST
+    );
+    if config.primitive_restart && list_topology {
+      return Err(PipelineInitError::PrimitiveRestartUnsupported {
+        pipeline: config.name.clone(),
+        topology: config.topology,
+      });
+    }
+
+    if config.has_tessellation() {
+      if config.topology != vk::PrimitiveTopology::PATCH_LIST {
+        return Err(PipelineInitError::TessellationWithoutPatchList {
+          pipeline: config.name.clone(),
+          topology: config.topology,
+        });
+      }
+      let max = limits.max_tessellation_patch_size;
+      if !(1..=max).contains(&config.patch_control_points) {
+        return Err(PipelineInitError::PatchControlPointsOutOfRange {
+          pipeline: config.name.clone(),
+          patch_control_points: config.patch_control_points,
+          max,
+        });
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Pipeline layout creation does not report oversized push constants, so check them up front
+  pub(crate) fn validate_push_constants(
+    name: &str,
+    push_constants: &[PushConstant],
+    max_size: u32,
+  ) -> Result<(), PipelineInitError> {
+    if let Some(push_constant) = push_constants
+      .iter()
+      .find(|push_constant| push_constant.offset % 4 != 0 || push_constant.size % 4 != 0)
+    {
+      return Err(PipelineInitError::PushConstantMisaligned {
+        pipeline: name.to_string(),
+        offset: push_constant.offset,
+        size: push_constant.size,
+      });
+    }
+
+    let size = push_constants
+      .iter()
+      .map(|push_constant| push_constant.offset.saturating_add(push_constant.size))
+      .max()
+      .unwrap_or(0);
+    if size > max_size {
+      return Err(PipelineInitError::PushConstantsTooLarge {
+        pipeline: name.to_string(),
+        size,
+        max: max_size,
+      });
+    }
+    Ok(())
+  }
+
+  fn cache_path(cache_dir: &Path, name: &str) -> PathBuf {
+    cache_dir.join(format!("{}.bin", name))
+  }
+
+  fn create_shader_cache(
+    logical_device: &ash::Device,
+    cache_path: &Path,
+    cache_header: &PipelineCacheHeader,
+  ) -> Result<vk::PipelineCache, vk::Result> {
+    let initial_data = match std::fs::read(cache_path) {
+      Ok(data) => cache_header.strip(&data).unwrap_or_default().to_vec(),
+      Err(_) => vec![],
     };
 
     let pipeline_cache_create_info =
@@ -432,9 +783,728 @@ impl Pipeline {
       logical_device.destroy_pipeline(self.pipeline, None);
       logical_device.destroy_pipeline_layout(self.pipeline_layout, None);
 
-      let pipeline_cache_data = logical_device.get_pipeline_cache_data(self.cache).unwrap();
-      std::fs::write(format!("cache/{}.bin", self.name), pipeline_cache_data).unwrap();
+      if let Err(err) = self.save_cache(logical_device) {
+        println!("Failed to save pipeline cache {:?}: {err}", self.cache_path);
+      }
       logical_device.destroy_pipeline_cache(self.cache, None);
     }
   }
+
+  fn save_cache(&self, logical_device: &ash::Device) -> Result<(), Error> {
+    let mut pipeline_cache_data = self.cache_header.to_bytes();
+    pipeline_cache_data.extend(unsafe { logical_device.get_pipeline_cache_data(self.cache) }?);
+    std::fs::write(&self.cache_path, pipeline_cache_data)?;
+    Ok(())
+  }
+}
+
+/// Identifies the device and driver a pipeline cache was written by, so caches from an older driver
+/// are discarded instead of handed to Vulkan
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) struct PipelineCacheHeader {
+  vendor_id: u32,
+  device_id: u32,
+  driver_version: u32,
+  cache_uuid: [u8; vk::UUID_SIZE],
+}
+
+impl PipelineCacheHeader {
+  const SIZE: usize = 3 * std::mem::size_of::<u32>() + vk::UUID_SIZE;
+
+  pub(crate) fn new(properties: &vk::PhysicalDeviceProperties) -> Self {
+    Self {
+      vendor_id: properties.vendor_id,
+      device_id: properties.device_id,
+      driver_version: properties.driver_version,
+      cache_uuid: properties.pipeline_cache_uuid,
+    }
+  }
+
+  fn to_bytes(self) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(Self::SIZE);
+    bytes.extend_from_slice(&self.vendor_id.to_le_bytes());
+    bytes.extend_from_slice(&self.device_id.to_le_bytes());
+    bytes.extend_from_slice(&self.driver_version.to_le_bytes());
+    bytes.extend_from_slice(&self.cache_uuid);
+    bytes
+  }
+
+  /// Returns the cache data following the header if the header matches this device
+  fn strip<'a>(&self, data: &'a [u8]) -> Option<&'a [u8]> {
+    if data.len() < Self::SIZE {
+      return None;
+    }
+    let (header, cache) = data.split_at(Self::SIZE);
+    (header == self.to_bytes().as_slice()).then_some(cache)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    mem::{offset_of, size_of},
+    path::PathBuf,
+  };
+
+  use ash::vk::{self, Handle};
+
+  use super::{
+    attachment_descriptions, init_render_pass, Pipeline, PipelineCacheHeader, PipelineManager,
+  };
+  use crate::{
+    config::vulkan::{
+      AttachmentOps, DepthBias, Descriptor, DescriptorSet, GraphicsPipelineConfig, PipelineType,
+      PushConstant, ShaderConfig, ShaderType, SpecConstant,
+    },
+    scene::mesh::Vertex,
+    vulkan::{
+      error::PipelineInitError,
+      headless::{Headless, OffscreenTarget},
+    },
+  };
+
+  fn config(topology: vk::PrimitiveTopology) -> GraphicsPipelineConfig {
+    GraphicsPipelineConfig::new("test".to_string(), topology, (800, 600))
+  }
+
+  fn tessellation_config(
+    topology: vk::PrimitiveTopology,
+    patch_control_points: u32,
+  ) -> GraphicsPipelineConfig {
+    config(topology)
+      .add_shader(ShaderConfig::new(ShaderType::TessellationControl, vec![]))
+      .add_shader(ShaderConfig::new(
+        ShaderType::TessellationEvaluation,
+        vec![],
+      ))
+      .set_patch_control_points(patch_control_points)
+  }
+
+  fn validate(pipeline: &GraphicsPipelineConfig) -> Result<(), PipelineInitError> {
+    let limits = vk::PhysicalDeviceLimits {
+      max_push_constants_size: 128,
+      max_tessellation_patch_size: 32,
+      ..Default::default()
+    };
+    Pipeline::validate_graphics_pipeline(pipeline, &limits)
+  }
+
+  #[test]
+  fn rasterization_state_applies_depth_bias() {
+    let pipeline =
+      config(vk::PrimitiveTopology::TRIANGLE_LIST).set_depth_bias(DepthBias::new(1.25, 1.75, 0.5));
+    let rasterizer_info = Pipeline::rasterization_state(&pipeline);
+    assert_eq!(rasterizer_info.depth_bias_enable, vk::TRUE);
+    assert_eq!(rasterizer_info.depth_bias_constant_factor, 1.25);
+    assert_eq!(rasterizer_info.depth_bias_slope_factor, 1.75);
+    assert_eq!(rasterizer_info.depth_bias_clamp, 0.5);
+  }
+
+  #[test]
+  fn rasterization_state_without_depth_bias() {
+    let rasterizer_info =
+      Pipeline::rasterization_state(&config(vk::PrimitiveTopology::TRIANGLE_LIST));
+    assert_eq!(rasterizer_info.depth_bias_enable, vk::FALSE);
+    assert_eq!(rasterizer_info.cull_mode, vk::CullModeFlags::BACK);
+    assert_eq!(rasterizer_info.polygon_mode, vk::PolygonMode::FILL);
+  }
+
+  #[test]
+  fn input_assembly_state_enables_primitive_restart() {
+    let pipeline = config(vk::PrimitiveTopology::LINE_STRIP).set_primitive_restart(true);
+    let input_assembly_info = Pipeline::input_assembly_state(&pipeline);
+    assert_eq!(
+      input_assembly_info.topology,
+      vk::PrimitiveTopology::LINE_STRIP
+    );
+    assert_eq!(input_assembly_info.primitive_restart_enable, vk::TRUE);
+    assert!(validate(&pipeline).is_ok());
+  }
+
+  #[test]
+  fn primitive_restart_is_rejected_for_lists() {
+    for topology in [
+      vk::PrimitiveTopology::POINT_LIST,
+      vk::PrimitiveTopology::TRIANGLE_LIST,
+      vk::PrimitiveTopology::PATCH_LIST,
+    ] {
+      let pipeline = config(topology).set_primitive_restart(true);
+      match validate(&pipeline) {
+        Err(PipelineInitError::PrimitiveRestartUnsupported {
+          topology: rejected, ..
+        }) => assert_eq!(rejected, topology),
+        result => panic!("unexpected result {result:?}"),
+      }
+    }
+  }
+
+  #[test]
+  fn reverse_z_keeps_greater_depth() {
+    let pipeline = config(vk::PrimitiveTopology::TRIANGLE_LIST);
+    let depth_stencil_info = Pipeline::depth_stencil_state(&pipeline, true);
+    assert_eq!(
+      depth_stencil_info.depth_compare_op,
+      vk::CompareOp::GREATER_OR_EQUAL
+    );
+    assert_eq!(depth_stencil_info.depth_test_enable, vk::TRUE);
+    assert_eq!(depth_stencil_info.depth_write_enable, vk::TRUE);
+
+    let depth_stencil_info = Pipeline::depth_stencil_state(&pipeline, false);
+    assert_eq!(
+      depth_stencil_info.depth_compare_op,
+      vk::CompareOp::LESS_OR_EQUAL
+    );
+  }
+
+  #[test]
+  fn ui_pipeline_disables_depth_test_and_write() {
+    let pipeline = config(vk::PrimitiveTopology::TRIANGLE_LIST)
+      .set_depth_test(false)
+      .set_depth_write(false);
+
+    let depth_stencil_info = Pipeline::depth_stencil_state(&pipeline, false);
+
+    assert_eq!(depth_stencil_info.depth_test_enable, vk::FALSE);
+    assert_eq!(depth_stencil_info.depth_write_enable, vk::FALSE);
+  }
+
+  #[test]
+  fn tessellation_needs_patch_list() {
+    let pipeline = tessellation_config(vk::PrimitiveTopology::TRIANGLE_LIST, 3);
+    assert!(matches!(
+      validate(&pipeline),
+      Err(PipelineInitError::TessellationWithoutPatchList { .. })
+    ));
+    assert!(validate(&tessellation_config(vk::PrimitiveTopology::PATCH_LIST, 3)).is_ok());
+  }
+
+  #[test]
+  fn patch_control_points_within_device_limit() {
+    for patch_control_points in [0, 33] {
+      let pipeline = tessellation_config(vk::PrimitiveTopology::PATCH_LIST, patch_control_points);
+      match validate(&pipeline) {
+        Err(PipelineInitError::PatchControlPointsOutOfRange {
+          patch_control_points: rejected,
+          max,
+          ..
+        }) => {
+          assert_eq!(rejected, patch_control_points);
+          assert_eq!(max, 32);
+        }
+        result => panic!("unexpected result {result:?}"),
+      }
+    }
+    assert!(validate(&tessellation_config(vk::PrimitiveTopology::PATCH_LIST, 32)).is_ok());
+  }
+
+  fn cache_header(
+    vendor_id: u32,
+    device_id: u32,
+    driver_version: u32,
+    cache_uuid: u8,
+  ) -> PipelineCacheHeader {
+    PipelineCacheHeader::new(&vk::PhysicalDeviceProperties {
+      vendor_id,
+      device_id,
+      driver_version,
+      pipeline_cache_uuid: [cache_uuid; vk::UUID_SIZE],
+      ..Default::default()
+    })
+  }
+
+  #[test]
+  fn cache_header_strips_matching_header() {
+    let header = cache_header(1, 2, 3, 4);
+    let mut data = header.to_bytes();
+    data.extend_from_slice(&[9, 8, 7]);
+    assert_eq!(header.strip(&data), Some([9, 8, 7].as_slice()));
+  }
+
+  #[test]
+  fn cache_header_rejects_other_device_or_driver() {
+    let header = cache_header(1, 2, 3, 4);
+    for other in [
+      cache_header(5, 2, 3, 4),
+      cache_header(1, 5, 3, 4),
+      cache_header(1, 2, 5, 4),
+      cache_header(1, 2, 3, 5),
+    ] {
+      let mut data = other.to_bytes();
+      data.extend_from_slice(&[9, 8, 7]);
+      assert_eq!(header.strip(&data), None);
+    }
+  }
+
+  #[test]
+  fn cache_header_rejects_short_data() {
+    let header = cache_header(1, 2, 3, 4);
+    let data = header.to_bytes();
+    assert_eq!(header.strip(&data[..PipelineCacheHeader::SIZE - 1]), None);
+    assert_eq!(header.strip(&[]), None);
+  }
+
+  #[test]
+  fn push_constants_over_device_limit_are_rejected() {
+    let pipeline = config(vk::PrimitiveTopology::TRIANGLE_LIST)
+      .add_push_constant(PushConstant::new(vk::ShaderStageFlags::VERTEX, 0, 256));
+
+    assert!(matches!(
+      validate(&pipeline),
+      Err(PipelineInitError::PushConstantsTooLarge {
+        size: 256,
+        max: 128,
+        ..
+      })
+    ));
+  }
+
+  #[test]
+  fn push_constants_within_device_limit_are_accepted() {
+    let pipeline = config(vk::PrimitiveTopology::TRIANGLE_LIST)
+      .add_push_constant(PushConstant::new(vk::ShaderStageFlags::VERTEX, 0, 64))
+      .add_push_constant(PushConstant::new(vk::ShaderStageFlags::FRAGMENT, 64, 64));
+
+    assert!(validate(&pipeline).is_ok());
+  }
+
+  #[test]
+  fn misaligned_push_constants_are_rejected() {
+    for (offset, size) in [(2, 16), (0, 6)] {
+      let pipeline = config(vk::PrimitiveTopology::TRIANGLE_LIST).add_push_constant(
+        PushConstant::new(vk::ShaderStageFlags::VERTEX, offset, size),
+      );
+
+      let result = validate(&pipeline);
+      assert!(
+        matches!(
+          result,
+          Err(PipelineInitError::PushConstantMisaligned { offset: o, size: s, .. })
+            if o == offset && s == size
+        ),
+        "{result:?}"
+      );
+    }
+  }
+
+  #[test]
+  fn cleared_attachments_start_undefined() {
+    let clear = AttachmentOps::new(vk::AttachmentLoadOp::CLEAR, vk::AttachmentStoreOp::STORE);
+
+    let [color, depth] = attachment_descriptions(vk::Format::B8G8R8A8_SRGB, clear, clear);
+
+    assert_eq!(color.format, vk::Format::B8G8R8A8_SRGB);
+    assert_eq!(color.load_op, vk::AttachmentLoadOp::CLEAR);
+    assert_eq!(color.initial_layout, vk::ImageLayout::UNDEFINED);
+    assert_eq!(color.final_layout, vk::ImageLayout::PRESENT_SRC_KHR);
+    assert_eq!(depth.format, vk::Format::D32_SFLOAT);
+    assert_eq!(depth.initial_layout, vk::ImageLayout::UNDEFINED);
+    assert_eq!(
+      depth.final_layout,
+      vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL
+    );
+  }
+
+  #[test]
+  fn loaded_attachments_keep_previous_layout() {
+    let load = AttachmentOps::new(vk::AttachmentLoadOp::LOAD, vk::AttachmentStoreOp::STORE);
+
+    let [color, depth] = attachment_descriptions(vk::Format::B8G8R8A8_SRGB, load, load);
+
+    assert_eq!(color.load_op, vk::AttachmentLoadOp::LOAD);
+    assert_eq!(color.initial_layout, vk::ImageLayout::PRESENT_SRC_KHR);
+    assert_eq!(depth.load_op, vk::AttachmentLoadOp::LOAD);
+    assert_eq!(
+      depth.initial_layout,
+      vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL
+    );
+  }
+
+  fn sampler_and_images(image_count: u32) -> DescriptorSet {
+    let stage = vk::ShaderStageFlags::FRAGMENT;
+    let descriptor_set = DescriptorSet::default().add_descriptor(Descriptor::new(
+      vk::DescriptorType::SAMPLER,
+      1,
+      stage,
+    ));
+    (0..image_count).fold(descriptor_set, |descriptor_set, _| {
+      descriptor_set.add_descriptor(Descriptor::new(vk::DescriptorType::SAMPLED_IMAGE, 1, stage))
+    })
+  }
+
+  #[test]
+  fn separate_sampler_and_sampled_image_bindings() {
+    let bindings = Pipeline::descriptor_set_layout_bindings(&sampler_and_images(3));
+
+    let layout: Vec<_> = bindings
+      .iter()
+      .map(|binding| {
+        (
+          binding.binding,
+          binding.descriptor_type,
+          binding.descriptor_count,
+        )
+      })
+      .collect();
+    assert_eq!(
+      layout,
+      vec![
+        (0, vk::DescriptorType::SAMPLER, 1),
+        (1, vk::DescriptorType::SAMPLED_IMAGE, 1),
+        (2, vk::DescriptorType::SAMPLED_IMAGE, 1),
+        (3, vk::DescriptorType::SAMPLED_IMAGE, 1),
+      ]
+    );
+    assert!(bindings
+      .iter()
+      .all(|binding| binding.stage_flags == vk::ShaderStageFlags::FRAGMENT));
+  }
+
+  #[test]
+  fn sampled_image_array_keeps_descriptor_count() {
+    let descriptor_set = DescriptorSet::default().add_descriptor(Descriptor::new(
+      vk::DescriptorType::SAMPLED_IMAGE,
+      3,
+      vk::ShaderStageFlags::FRAGMENT,
+    ));
+
+    let bindings = Pipeline::descriptor_set_layout_bindings(&descriptor_set);
+
+    assert_eq!(bindings.len(), 1);
+    assert_eq!(bindings[0].descriptor_count, 3);
+  }
+
+  #[test]
+  fn creates_sampler_and_sampled_image_layout() {
+    let Some(headless) = Headless::init(&[]) else {
+      return;
+    };
+
+    let layouts =
+      Pipeline::get_descriptor_set_layouts(&vec![sampler_and_images(3)], &headless.device).unwrap();
+
+    assert_eq!(layouts.len(), 1);
+    assert_ne!(layouts[0], vk::DescriptorSetLayout::null());
+    unsafe {
+      headless
+        .device
+        .destroy_descriptor_set_layout(layouts[0], None)
+    };
+  }
+
+  #[test]
+  fn default_vertex_binding_matches_vertex_layout() {
+    let pipeline = Pipeline::default_shader(&vk::Extent2D::default());
+    let (attributes, bindings) = Pipeline::vertex_input_descriptions(&pipeline.input);
+
+    assert_eq!(bindings[0].stride as usize, size_of::<Vertex>());
+    let vertex_offsets: Vec<_> = attributes
+      .iter()
+      .filter(|attribute| attribute.binding == 0)
+      .map(|attribute| attribute.offset as usize)
+      .collect();
+    assert_eq!(
+      vertex_offsets,
+      vec![
+        offset_of!(Vertex, position),
+        offset_of!(Vertex, normal),
+        offset_of!(Vertex, colour),
+        offset_of!(Vertex, uv),
+      ]
+    );
+  }
+
+  fn named(name: &str, base: Option<&str>) -> GraphicsPipelineConfig {
+    let mut pipeline = config(vk::PrimitiveTopology::TRIANGLE_LIST);
+    pipeline.name = name.to_string();
+    pipeline.base_pipeline = base.map(str::to_string);
+    pipeline
+  }
+
+  #[test]
+  fn derivative_resolves_earlier_base() {
+    let base = named("base", None);
+    let derived = named("derived", Some("base"));
+
+    let indices = PipelineManager::base_pipeline_indices(&[&base, &derived]);
+    assert_eq!(indices.unwrap(), vec![None, Some(0)]);
+  }
+
+  #[test]
+  fn derivative_before_base_is_rejected() {
+    let base = named("base", None);
+    let derived = named("derived", Some("base"));
+
+    let result = PipelineManager::base_pipeline_indices(&[&derived, &base]);
+    assert!(matches!(
+      result,
+      Err(PipelineInitError::BasePipelineMissing { pipeline, base })
+        if pipeline == "derived" && base == "base"
+    ));
+  }
+
+  /// Headless device with a render pass and a pipeline cache directory of its own, so tests don't
+  /// touch the caches of the app
+  struct Fixture {
+    headless: Headless,
+    render_pass: vk::RenderPass,
+    cache_dir: PathBuf,
+  }
+
+  impl Fixture {
+    fn init(test: &str) -> Option<Self> {
+      let headless = Headless::init(&[])?;
+      let color_ops = AttachmentOps::new(vk::AttachmentLoadOp::CLEAR, vk::AttachmentStoreOp::STORE);
+      let render_pass = init_render_pass(
+        &headless.device,
+        vk::Format::B8G8R8A8_SRGB,
+        color_ops,
+        color_ops,
+      )
+      .unwrap();
+      let cache_dir =
+        std::env::temp_dir().join(format!("vulkan_engine_{test}_{}", std::process::id()));
+      Some(Self {
+        headless,
+        render_pass,
+        cache_dir,
+      })
+    }
+
+    fn extent() -> vk::Extent2D {
+      vk::Extent2D::default().width(800).height(600)
+    }
+
+    fn manager(&self, mut pipelines: Vec<PipelineType>) -> PipelineManager {
+      PipelineManager::init(
+        &self.headless.device,
+        self.render_pass,
+        &Self::extent(),
+        &mut pipelines,
+        false,
+        &vk::PhysicalDeviceProperties::default(),
+        &self.cache_dir,
+      )
+      .unwrap()
+    }
+  }
+
+  impl Drop for Fixture {
+    fn drop(&mut self) {
+      unsafe {
+        self
+          .headless
+          .device
+          .destroy_render_pass(self.render_pass, None)
+      };
+      let _ = std::fs::remove_dir_all(&self.cache_dir);
+      let _ = std::fs::remove_file(&self.cache_dir);
+    }
+  }
+
+  #[test]
+  fn creates_base_and_derivative_pipeline() {
+    let Some(fixture) = Fixture::init("derivative") else {
+      return;
+    };
+    let mut base = Pipeline::default_shader(&Fixture::extent());
+    base.name = "test_base".to_string();
+    let mut derived =
+      Pipeline::default_shader(&Fixture::extent()).set_base_pipeline("test_base".to_string());
+    derived.name = "test_derived".to_string();
+
+    let mut manager = fixture.manager(vec![
+      PipelineType::Graphics(base),
+      PipelineType::Graphics(derived),
+    ]);
+
+    let names: Vec<_> = manager.pipelines.iter().map(Pipeline::get_name).collect();
+    assert_eq!(names, vec!["test_base", "test_derived", "default"]);
+    assert!(manager
+      .pipelines
+      .iter()
+      .all(|pipeline| pipeline.pipeline != vk::Pipeline::null()));
+    manager.destroy();
+    for name in names {
+      assert!(fixture.cache_dir.join(format!("{name}.bin")).exists());
+    }
+  }
+
+  fn tinted(name: &str, brightness: f32) -> PipelineType {
+    let fragment_shader = ShaderConfig::new(
+      ShaderType::Fragment,
+      vk_shader_macros::include_glsl!("./shaders/tint.frag").to_vec(),
+    )
+    .add_specialization(0, SpecConstant::Float(brightness));
+    PipelineType::Graphics(GraphicsPipelineConfig::fullscreen_triangle(
+      name.to_string(),
+      fragment_shader,
+      (800, 600),
+    ))
+  }
+
+  #[test]
+  fn specialized_shader_creates_distinct_pipelines() {
+    let Some(fixture) = Fixture::init("specialization") else {
+      return;
+    };
+
+    let manager = fixture.manager(vec![tinted("dim", 0.5), tinted("bright", 2.0)]);
+
+    let dim = &manager.pipelines[0];
+    let bright = &manager.pipelines[1];
+    assert_eq!(dim.get_name(), "dim");
+    assert_eq!(bright.get_name(), "bright");
+    assert_ne!(dim.pipeline, vk::Pipeline::null());
+    assert_ne!(bright.pipeline, vk::Pipeline::null());
+    assert_ne!(dim.pipeline, bright.pipeline);
+  }
+
+  #[test]
+  fn records_fullscreen_triangle_without_vertex_buffer() {
+    let Some(fixture) = Fixture::init("fullscreen") else {
+      return;
+    };
+    let headless = &fixture.headless;
+    if !headless.supports_pipeline_statistics() {
+      return;
+    }
+    let manager = fixture.manager(vec![tinted("fullscreen", 1.0)]);
+    let target = OffscreenTarget::init(
+      headless,
+      fixture.render_pass,
+      vk::Format::B8G8R8A8_SRGB,
+      Fixture::extent(),
+    );
+    let query_pool_info = vk::QueryPoolCreateInfo::default()
+      .query_type(vk::QueryType::PIPELINE_STATISTICS)
+      .query_count(1)
+      .pipeline_statistics(vk::QueryPipelineStatisticFlags::INPUT_ASSEMBLY_VERTICES);
+    let query_pool = unsafe { headless.device.create_query_pool(&query_pool_info, None) }.unwrap();
+
+    headless.submit_and_wait(|command_buffer| unsafe {
+      let device = &headless.device;
+      device.cmd_reset_query_pool(command_buffer, query_pool, 0, 1);
+      let clear_values = [
+        vk::ClearValue::default(),
+        vk::ClearValue {
+          depth_stencil: vk::ClearDepthStencilValue::default().depth(1.0),
+        },
+      ];
+      let render_pass_info = vk::RenderPassBeginInfo::default()
+        .render_pass(fixture.render_pass)
+        .framebuffer(target.framebuffer)
+        .render_area(Fixture::extent().into())
+        .clear_values(&clear_values);
+      device.cmd_begin_render_pass(
+        command_buffer,
+        &render_pass_info,
+        vk::SubpassContents::INLINE,
+      );
+      device.cmd_begin_query(
+        command_buffer,
+        query_pool,
+        0,
+        vk::QueryControlFlags::empty(),
+      );
+      manager.pipelines[0].record_fullscreen(device, command_buffer);
+      device.cmd_end_query(command_buffer, query_pool, 0);
+      device.cmd_end_render_pass(command_buffer);
+    });
+
+    let mut vertices = [0u64];
+    unsafe {
+      headless
+        .device
+        .get_query_pool_results(
+          query_pool,
+          0,
+          &mut vertices,
+          vk::QueryResultFlags::TYPE_64 | vk::QueryResultFlags::WAIT,
+        )
+        .unwrap();
+      headless.device.destroy_query_pool(query_pool, None);
+    }
+    target.destroy(&headless.device);
+    assert_eq!(vertices, [3]);
+  }
+
+  fn depth_only() -> GraphicsPipelineConfig {
+    let mut pipeline = Pipeline::default_shader(&Fixture::extent());
+    pipeline.name = "depth_only".to_string();
+    pipeline
+      .shaders
+      .retain(|shader| shader.type_ == vk::ShaderStageFlags::VERTEX);
+    pipeline
+  }
+
+  #[test]
+  fn depth_only_pipeline_writes_no_color() {
+    let pipeline = depth_only();
+    assert!(pipeline.is_depth_only());
+    assert!(Pipeline::color_blend_attachment(&pipeline)
+      .color_write_mask
+      .is_empty());
+    let depth_stencil_info = Pipeline::depth_stencil_state(&pipeline, false);
+    assert_eq!(depth_stencil_info.depth_write_enable, vk::TRUE);
+  }
+
+  #[test]
+  fn depth_only_pipeline_without_depth_write_is_rejected() {
+    let pipeline = depth_only().set_depth_write(false);
+    let result =
+      Pipeline::validate_graphics_pipeline(&pipeline, &vk::PhysicalDeviceLimits::default());
+    assert!(matches!(
+      result,
+      Err(PipelineInitError::DepthOnlyWithoutDepthWrite(name)) if name == "depth_only"
+    ));
+  }
+
+  #[test]
+  fn creates_depth_only_pipeline() {
+    let Some(fixture) = Fixture::init("depth_only") else {
+      return;
+    };
+
+    let manager = fixture.manager(vec![PipelineType::Graphics(depth_only())]);
+    assert_eq!(manager.pipelines[0].get_name(), "depth_only");
+    assert_ne!(manager.pipelines[0].pipeline, vk::Pipeline::null());
+  }
+
+  #[test]
+  fn drop_without_destroy_saves_cache() {
+    let Some(fixture) = Fixture::init("drop") else {
+      return;
+    };
+
+    drop(fixture.manager(vec![]));
+    assert!(fixture.cache_dir.join("default.bin").exists());
+  }
+
+  #[test]
+  fn drop_after_destroy_does_nothing() {
+    let Some(fixture) = Fixture::init("destroy_drop") else {
+      return;
+    };
+    let cache = fixture.cache_dir.join("default.bin");
+
+    let mut manager = fixture.manager(vec![]);
+    manager.destroy();
+    std::fs::remove_file(&cache).unwrap();
+    manager.destroy();
+    drop(manager);
+    assert!(!cache.exists());
+  }
+
+  #[test]
+  fn unwritable_cache_dir_does_not_panic() {
+    let Some(fixture) = Fixture::init("unwritable") else {
+      return;
+    };
+    // a file where the directory should be makes every cache write fail
+    std::fs::write(&fixture.cache_dir, []).unwrap();
+
+    let mut manager = fixture.manager(vec![]);
+    manager.destroy();
+    assert!(fixture.cache_dir.is_file());
+  }
 }
@@ -1,7 +1,11 @@
+use anyhow::Error;
 use ash::{khr, vk};
 use gpu_allocator::vulkan;
 
-use crate::{config::app::AppConfig, vulkan::{device::QueueFamilies, surface::Surface}};
+use crate::{
+  config::app::AppConfig,
+  vulkan::{device::QueueFamilies, error::RendererInitError, surface::Surface},
+};
 
 pub(crate) struct SwapChain {
   loader: khr::swapchain::Device,
@@ -12,13 +16,10 @@ pub(crate) struct SwapChain {
   depth_image_allocation: vulkan::Allocation,
   depth_image_view: vk::ImageView,
   frame_buffers: Vec<vk::Framebuffer>,
-  //surface_format: vk::SurfaceFormatKHR,
+  surface_format: vk::SurfaceFormatKHR,
   extent: vk::Extent2D,
-  image_available: Vec<vk::Semaphore>,
-  render_finished: Vec<vk::Semaphore>,
-  may_begin_drawing: Vec<vk::Fence>,
+  frame_sync: Vec<FrameSync>,
   amount_of_images: u32,
-  current_image: usize,
 }
 
 impl SwapChain {
@@ -30,10 +31,14 @@ impl SwapChain {
     queue_families: &QueueFamilies,
     allocator: &mut vulkan::Allocator,
     config: &AppConfig,
-  ) -> Result<Self, vk::Result> {
+  ) -> Result<Self, Error> {
     let surface_capabilities = surfaces.get_capabilities(physical_device)?;
     //let surface_present_modes = surfaces.get_present_modes(physical_device)?;
-    let surface_format = *surfaces.get_formats(physical_device)?.first().unwrap();
+    let surface_format = Self::select_surface_format(
+      instance,
+      physical_device,
+      &surfaces.get_formats(physical_device)?,
+    )?;
 
     let mut extent = surface_capabilities.current_extent;
     if extent.width == u32::MAX || extent.height == u32::MAX {
@@ -42,13 +47,7 @@ impl SwapChain {
     }
 
     let queue_families = [queue_families.get_graphics_q_index()];
-    let image_count =
-      if surface_capabilities.min_image_count <= surface_capabilities.max_image_count {
-        3.max(surface_capabilities.min_image_count)
-          .min(surface_capabilities.max_image_count)
-      } else {
-        surface_capabilities.min_image_count
-      };
+    let image_count = Self::clamp_image_count(config.desired_image_count, &surface_capabilities);
     let swapchain_create_info = vk::SwapchainCreateInfoKHR::default()
       .surface(surfaces.get_surface())
       .min_image_count(image_count)
@@ -135,21 +134,8 @@ impl SwapChain {
     let depth_image_view =
       unsafe { logical_device.create_image_view(&depth_image_view_create_info, None) }?;
 
-    let mut image_available = Vec::new();
-    let mut render_finished = Vec::new();
-    let mut may_begin_drawing = Vec::new();
-    let semaphore_create_info = vk::SemaphoreCreateInfo::default();
-    let fence_create_info = vk::FenceCreateInfo::default().flags(vk::FenceCreateFlags::SIGNALED);
-    for _ in 0..amount_of_images {
-      let image_available_semaphore =
-        unsafe { logical_device.create_semaphore(&semaphore_create_info, None) }?;
-      let render_finished_semaphore =
-        unsafe { logical_device.create_semaphore(&semaphore_create_info, None) }?;
-      image_available.push(image_available_semaphore);
-      render_finished.push(render_finished_semaphore);
-      let fence = unsafe { logical_device.create_fence(&fence_create_info, None) }?;
-      may_begin_drawing.push(fence);
-    }
+    let frames_in_flight = config.frames_in_flight.max(1) as usize;
+    let frame_sync = FrameSync::init(logical_device, frames_in_flight)?;
 
     Ok(Self {
       loader: swapchain_loader,
@@ -157,23 +143,81 @@ impl SwapChain {
       images: swapchain_images,
       image_views: swapchain_image_views,
       frame_buffers: Vec::new(),
-      //surface_format,
+      surface_format,
       extent,
-      image_available,
-      render_finished,
+      frame_sync,
       amount_of_images,
-      current_image: 0,
-      may_begin_drawing,
       depth_image,
       depth_image_allocation,
       depth_image_view,
     })
   }
 
+  /// A max image count of 0 means the surface has no upper limit
+  pub(crate) fn clamp_image_count(
+    desired_image_count: u32,
+    surface_capabilities: &vk::SurfaceCapabilitiesKHR,
+  ) -> u32 {
+    let image_count = desired_image_count.max(surface_capabilities.min_image_count);
+    if surface_capabilities.max_image_count == 0 {
+      image_count
+    } else {
+      image_count.min(surface_capabilities.max_image_count)
+    }
+  }
+
+  pub(crate) fn select_surface_format(
+    instance: &ash::Instance,
+    physical_device: vk::PhysicalDevice,
+    formats: &[vk::SurfaceFormatKHR],
+  ) -> Result<vk::SurfaceFormatKHR, RendererInitError> {
+    Self::select_usable_format(formats, |format| {
+      let properties =
+        unsafe { instance.get_physical_device_format_properties(physical_device, format) };
+      properties
+        .optimal_tiling_features
+        .contains(vk::FormatFeatureFlags::COLOR_ATTACHMENT)
+    })
+  }
+
+  fn select_usable_format(
+    formats: &[vk::SurfaceFormatKHR],
+    is_color_attachment: impl Fn(vk::Format) -> bool,
+  ) -> Result<vk::SurfaceFormatKHR, RendererInitError> {
+    if formats.is_empty() {
+      return Err(RendererInitError::FormatMissing);
+    }
+
+    formats
+      .iter()
+      .find(|format| format.format != vk::Format::UNDEFINED && is_color_attachment(format.format))
+      .copied()
+      .ok_or_else(|| {
+        RendererInitError::FormatUnsupported(formats.iter().map(|format| format.format).collect())
+      })
+  }
+
+  pub(crate) fn get_surface_format(&self) -> vk::SurfaceFormatKHR {
+    self.surface_format
+  }
+
   pub(crate) fn get_extent(&self) -> vk::Extent2D {
     self.extent
   }
 
+  /// The number of images the driver actually created, can be more than requested
+  pub(crate) fn get_image_count(&self) -> u32 {
+    self.amount_of_images
+  }
+
+  pub(crate) fn get_images(&self) -> &[vk::Image] {
+    &self.images
+  }
+
+  pub(crate) fn get_depth_image(&self) -> vk::Image {
+    self.depth_image
+  }
+
   pub(crate) fn destroy(
     &mut self,
     logical_device: &ash::Device,
@@ -182,18 +226,12 @@ impl SwapChain {
     unsafe {
       logical_device.destroy_image_view(self.depth_image_view, None);
       logical_device.destroy_image(self.depth_image, None);
-      allocator
-        .free(std::mem::take(&mut self.depth_image_allocation))
-        .unwrap();
-
-      for fence in &self.may_begin_drawing {
-        logical_device.destroy_fence(*fence, None);
+      if let Err(err) = allocator.free(std::mem::take(&mut self.depth_image_allocation)) {
+        println!("Failed to free depth image memory: {err}");
       }
-      for semaphore in &self.image_available {
-        logical_device.destroy_semaphore(*semaphore, None);
-      }
-      for semaphore in &self.render_finished {
-        logical_device.destroy_semaphore(*semaphore, None);
+
+      for frame_sync in &self.frame_sync {
+        frame_sync.destroy(logical_device);
       }
       for frame_buffer in &self.frame_buffers {
         logical_device.destroy_framebuffer(*frame_buffer, None);
@@ -225,3 +263,149 @@ impl SwapChain {
     Ok(())
   }
 }
+
+/// Synchronization objects of one frame in flight
+#[derive(Clone, Copy)]
+pub(crate) struct FrameSync {
+  pub(crate) image_available: vk::Semaphore,
+  pub(crate) render_finished: vk::Semaphore,
+  pub(crate) may_begin_drawing: vk::Fence,
+}
+
+impl FrameSync {
+  /// The fences start signaled so the first frame doesn't wait on work that was never submitted
+  fn init(logical_device: &ash::Device, frames_in_flight: usize) -> Result<Vec<Self>, vk::Result> {
+    let semaphore_create_info = vk::SemaphoreCreateInfo::default();
+    let fence_create_info = vk::FenceCreateInfo::default().flags(vk::FenceCreateFlags::SIGNALED);
+    let mut frame_sync = Vec::with_capacity(frames_in_flight);
+    for _ in 0..frames_in_flight {
+      frame_sync.push(Self {
+        image_available: unsafe { logical_device.create_semaphore(&semaphore_create_info, None) }?,
+        render_finished: unsafe { logical_device.create_semaphore(&semaphore_create_info, None) }?,
+        may_begin_drawing: unsafe { logical_device.create_fence(&fence_create_info, None) }?,
+      });
+    }
+    Ok(frame_sync)
+  }
+
+  fn destroy(&self, logical_device: &ash::Device) {
+    unsafe {
+      logical_device.destroy_fence(self.may_begin_drawing, None);
+      logical_device.destroy_semaphore(self.image_available, None);
+      logical_device.destroy_semaphore(self.render_finished, None);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::collections::HashSet;
+
+  use ash::vk::{self, Handle};
+
+  use super::{FrameSync, SwapChain};
+  use crate::vulkan::{error::RendererInitError, headless::Headless};
+
+  fn surface_format(format: vk::Format) -> vk::SurfaceFormatKHR {
+    vk::SurfaceFormatKHR {
+      format,
+      color_space: vk::ColorSpaceKHR::SRGB_NONLINEAR,
+    }
+  }
+
+  #[test]
+  fn creates_sync_objects_per_frame_in_flight() {
+    let Some(headless) = Headless::init(&[]) else {
+      return;
+    };
+    let frame_sync = FrameSync::init(&headless.device, 3).unwrap();
+
+    let semaphores: HashSet<_> = frame_sync
+      .iter()
+      .flat_map(|frame| {
+        [
+          frame.image_available.as_raw(),
+          frame.render_finished.as_raw(),
+        ]
+      })
+      .collect();
+    let fences: HashSet<_> = frame_sync
+      .iter()
+      .map(|frame| frame.may_begin_drawing.as_raw())
+      .collect();
+    let signaled = frame_sync.iter().all(|frame| {
+      let status = unsafe { headless.device.get_fence_status(frame.may_begin_drawing) };
+      status == Ok(true)
+    });
+    for frame in &frame_sync {
+      frame.destroy(&headless.device);
+    }
+
+    assert_eq!(frame_sync.len(), 3);
+    assert_eq!(semaphores.len(), 6);
+    assert_eq!(fences.len(), 3);
+    assert!(signaled);
+    assert_ne!(frame_sync[0].may_begin_drawing, vk::Fence::null());
+  }
+
+  #[test]
+  fn selects_first_usable_format() {
+    let formats = [
+      surface_format(vk::Format::UNDEFINED),
+      surface_format(vk::Format::R8G8B8A8_UNORM),
+      surface_format(vk::Format::B8G8R8A8_SRGB),
+    ];
+    let selected =
+      SwapChain::select_usable_format(&formats, |format| format == vk::Format::B8G8R8A8_SRGB)
+        .unwrap();
+    assert_eq!(selected.format, vk::Format::B8G8R8A8_SRGB);
+  }
+
+  #[test]
+  fn unusable_formats_are_listed() {
+    let formats = [
+      surface_format(vk::Format::B8G8R8A8_SRGB),
+      surface_format(vk::Format::R8G8B8A8_UNORM),
+    ];
+    match SwapChain::select_usable_format(&formats, |_| false) {
+      Err(RendererInitError::FormatUnsupported(listed)) => assert_eq!(
+        listed,
+        vec![vk::Format::B8G8R8A8_SRGB, vk::Format::R8G8B8A8_UNORM]
+      ),
+      result => panic!("unexpected result {result:?}"),
+    }
+    assert!(matches!(
+      SwapChain::select_usable_format(&[], |_| true),
+      Err(RendererInitError::FormatMissing)
+    ));
+  }
+
+  fn capabilities(min_image_count: u32, max_image_count: u32) -> vk::SurfaceCapabilitiesKHR {
+    vk::SurfaceCapabilitiesKHR {
+      min_image_count,
+      max_image_count,
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn image_count_below_min_is_raised() {
+    assert_eq!(SwapChain::clamp_image_count(1, &capabilities(2, 8)), 2);
+  }
+
+  #[test]
+  fn image_count_above_max_is_lowered() {
+    assert_eq!(SwapChain::clamp_image_count(16, &capabilities(2, 8)), 8);
+  }
+
+  #[test]
+  fn image_count_within_limits_is_kept() {
+    assert_eq!(SwapChain::clamp_image_count(3, &capabilities(2, 8)), 3);
+  }
+
+  #[test]
+  fn image_count_without_max_is_unbounded() {
+    assert_eq!(SwapChain::clamp_image_count(16, &capabilities(2, 0)), 16);
+    assert_eq!(SwapChain::clamp_image_count(1, &capabilities(2, 0)), 2);
+  }
+}
@@ -0,0 +1,286 @@
+use std::ffi::CStr;
+
+use ash::{khr, vk};
+
+use super::instance::select_physical_device;
+use crate::config::vulkan::DevicePreference;
+
+/// Instance and logical device without a surface for tests that need a driver
+pub(crate) struct Headless {
+  #[allow(dead_code)]
+  pub(crate) entry: ash::Entry,
+  pub(crate) instance: ash::Instance,
+  pub(crate) physical_device: vk::PhysicalDevice,
+  pub(crate) device: ash::Device,
+  pub(crate) queue_family: u32,
+  pub(crate) queue: vk::Queue,
+}
+
+impl Headless {
+  /// `None` if there is no Vulkan driver or it lacks one of the instance extensions, tests
+  /// return early in that case
+  pub(crate) fn init(instance_extensions: &[&CStr]) -> Option<Self> {
+    let entry = unsafe { ash::Entry::load() }.ok()?;
+    let extension_names: Vec<_> = instance_extensions
+      .iter()
+      .map(|extension| extension.as_ptr())
+      .collect();
+    let app_info = vk::ApplicationInfo::default().api_version(vk::make_api_version(0, 1, 3, 0));
+    let instance_info = vk::InstanceCreateInfo::default()
+      .application_info(&app_info)
+      .enabled_extension_names(&extension_names);
+    let instance = unsafe { entry.create_instance(&instance_info, None) }.ok()?;
+
+    let device =
+      Self::select_graphics_device(&instance).and_then(|(physical_device, queue_family)| {
+        let device = Self::create_device(&instance, physical_device, queue_family)?;
+        Some((physical_device, queue_family, device))
+      });
+    let Some((physical_device, queue_family, device)) = device else {
+      unsafe { instance.destroy_instance(None) };
+      return None;
+    };
+    let queue = unsafe { device.get_device_queue(queue_family, 0) };
+
+    Some(Self {
+      entry,
+      instance,
+      physical_device,
+      device,
+      queue_family,
+      queue,
+    })
+  }
+
+  /// Picks among the devices with a graphics queue like the renderer does, `VULKAN_DEVICE` selects
+  /// the preferred kind, e.g. `software` for lavapipe in CI
+  fn select_graphics_device(instance: &ash::Instance) -> Option<(vk::PhysicalDevice, u32)> {
+    let candidates: Vec<_> = unsafe { instance.enumerate_physical_devices() }
+      .ok()?
+      .into_iter()
+      .filter_map(|physical_device| {
+        let queue_family = Self::graphics_queue_family(instance, physical_device)?;
+        Some((physical_device, queue_family))
+      })
+      .collect();
+    let device_types: Vec<_> = candidates
+      .iter()
+      .map(|(physical_device, _)| {
+        unsafe { instance.get_physical_device_properties(*physical_device) }.device_type
+      })
+      .collect();
+
+    let preference = DevicePreference::from_env().unwrap_or_default();
+    select_physical_device(&device_types, preference).map(|index| candidates[index])
+  }
+
+  fn graphics_queue_family(
+    instance: &ash::Instance,
+    physical_device: vk::PhysicalDevice,
+  ) -> Option<u32> {
+    unsafe { instance.get_physical_device_queue_family_properties(physical_device) }
+      .iter()
+      .position(|family| family.queue_flags.contains(vk::QueueFlags::GRAPHICS))
+      .map(|index| index as u32)
+  }
+
+  /// Enables the swap chain extension, which the render pass needs for its present layout, and
+  /// pipeline statistics queries if the device has them
+  fn create_device(
+    instance: &ash::Instance,
+    physical_device: vk::PhysicalDevice,
+    queue_family: u32,
+  ) -> Option<ash::Device> {
+    let has_swap_chain = unsafe { instance.enumerate_device_extension_properties(physical_device) }
+      .ok()?
+      .iter()
+      .any(|extension| extension.extension_name_as_c_str() == Ok(khr::swapchain::NAME));
+    let extension_names = if has_swap_chain {
+      vec![khr::swapchain::NAME.as_ptr()]
+    } else {
+      vec![]
+    };
+    let supported_features = unsafe { instance.get_physical_device_features(physical_device) };
+    let features = vk::PhysicalDeviceFeatures::default()
+      .pipeline_statistics_query(supported_features.pipeline_statistics_query == vk::TRUE);
+
+    let priorities = [1.0];
+    let queue_infos = [vk::DeviceQueueCreateInfo::default()
+      .queue_family_index(queue_family)
+      .queue_priorities(&priorities)];
+    let device_info = vk::DeviceCreateInfo::default()
+      .queue_create_infos(&queue_infos)
+      .enabled_extension_names(&extension_names)
+      .enabled_features(&features);
+    unsafe { instance.create_device(physical_device, &device_info, None) }.ok()
+  }
+
+  pub(crate) fn supports_pipeline_statistics(&self) -> bool {
+    let features = unsafe {
+      self
+        .instance
+        .get_physical_device_features(self.physical_device)
+    };
+    features.pipeline_statistics_query == vk::TRUE
+  }
+
+  /// Records one command buffer, submits it to the queue and waits until it has finished
+  pub(crate) fn submit_and_wait(&self, record: impl FnOnce(vk::CommandBuffer)) {
+    let pool_info = vk::CommandPoolCreateInfo::default().queue_family_index(self.queue_family);
+    let command_pool = unsafe { self.device.create_command_pool(&pool_info, None) }.unwrap();
+    let buffer_info = vk::CommandBufferAllocateInfo::default()
+      .command_pool(command_pool)
+      .level(vk::CommandBufferLevel::PRIMARY)
+      .command_buffer_count(1);
+    let command_buffer = unsafe { self.device.allocate_command_buffers(&buffer_info) }.unwrap()[0];
+    let fence = unsafe {
+      self
+        .device
+        .create_fence(&vk::FenceCreateInfo::default(), None)
+    }
+    .unwrap();
+
+    unsafe {
+      let begin_info =
+        vk::CommandBufferBeginInfo::default().flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+      self
+        .device
+        .begin_command_buffer(command_buffer, &begin_info)
+        .unwrap();
+      record(command_buffer);
+      self.device.end_command_buffer(command_buffer).unwrap();
+
+      let command_buffers = [command_buffer];
+      let submit_info = [vk::SubmitInfo::default().command_buffers(&command_buffers)];
+      self
+        .device
+        .queue_submit(self.queue, &submit_info, fence)
+        .unwrap();
+      self
+        .device
+        .wait_for_fences(&[fence], true, u64::MAX)
+        .unwrap();
+
+      self.device.destroy_fence(fence, None);
+      self.device.destroy_command_pool(command_pool, None);
+    }
+  }
+}
+
+impl Drop for Headless {
+  fn drop(&mut self) {
+    unsafe {
+      self.device.destroy_device(None);
+      self.instance.destroy_instance(None);
+    }
+  }
+}
+
+/// Color and depth image with a framebuffer for a render pass from `init_render_pass`, in place of
+/// the swap chain images
+pub(crate) struct OffscreenTarget {
+  images: [vk::Image; 2],
+  memory: [vk::DeviceMemory; 2],
+  views: [vk::ImageView; 2],
+  pub(crate) framebuffer: vk::Framebuffer,
+}
+
+impl OffscreenTarget {
+  pub(crate) fn init(
+    headless: &Headless,
+    render_pass: vk::RenderPass,
+    color_format: vk::Format,
+    extent: vk::Extent2D,
+  ) -> Self {
+    let (color_image, color_memory, color_view) = Self::attachment(
+      headless,
+      color_format,
+      vk::ImageUsageFlags::COLOR_ATTACHMENT,
+      vk::ImageAspectFlags::COLOR,
+      extent,
+    );
+    let (depth_image, depth_memory, depth_view) = Self::attachment(
+      headless,
+      vk::Format::D32_SFLOAT,
+      vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+      vk::ImageAspectFlags::DEPTH,
+      extent,
+    );
+
+    let views = [color_view, depth_view];
+    let framebuffer_info = vk::FramebufferCreateInfo::default()
+      .render_pass(render_pass)
+      .attachments(&views)
+      .width(extent.width)
+      .height(extent.height)
+      .layers(1);
+    let framebuffer =
+      unsafe { headless.device.create_framebuffer(&framebuffer_info, None) }.unwrap();
+
+    Self {
+      images: [color_image, depth_image],
+      memory: [color_memory, depth_memory],
+      views,
+      framebuffer,
+    }
+  }
+
+  fn attachment(
+    headless: &Headless,
+    format: vk::Format,
+    usage: vk::ImageUsageFlags,
+    aspect: vk::ImageAspectFlags,
+    extent: vk::Extent2D,
+  ) -> (vk::Image, vk::DeviceMemory, vk::ImageView) {
+    let device = &headless.device;
+    let image_info = vk::ImageCreateInfo::default()
+      .image_type(vk::ImageType::TYPE_2D)
+      .format(format)
+      .extent(extent.into())
+      .mip_levels(1)
+      .array_layers(1)
+      .samples(vk::SampleCountFlags::TYPE_1)
+      .tiling(vk::ImageTiling::OPTIMAL)
+      .usage(usage);
+    let image = unsafe { device.create_image(&image_info, None) }.unwrap();
+
+    let requirements = unsafe { device.get_image_memory_requirements(image) };
+    let memory_type = (0..32)
+      .find(|i| requirements.memory_type_bits & (1 << i) != 0)
+      .unwrap();
+    let allocate_info = vk::MemoryAllocateInfo::default()
+      .allocation_size(requirements.size)
+      .memory_type_index(memory_type);
+    let memory = unsafe { device.allocate_memory(&allocate_info, None) }.unwrap();
+    unsafe { device.bind_image_memory(image, memory, 0) }.unwrap();
+
+    let view_info = vk::ImageViewCreateInfo::default()
+      .image(image)
+      .view_type(vk::ImageViewType::TYPE_2D)
+      .format(format)
+      .subresource_range(
+        vk::ImageSubresourceRange::default()
+          .aspect_mask(aspect)
+          .level_count(1)
+          .layer_count(1),
+      );
+    let view = unsafe { device.create_image_view(&view_info, None) }.unwrap();
+
+    (image, memory, view)
+  }
+
+  pub(crate) fn destroy(&self, device: &ash::Device) {
+    unsafe {
+      device.destroy_framebuffer(self.framebuffer, None);
+      for view in self.views {
+        device.destroy_image_view(view, None);
+      }
+      for image in self.images {
+        device.destroy_image(image, None);
+      }
+      for memory in self.memory {
+        device.free_memory(memory, None);
+      }
+    }
+  }
+}
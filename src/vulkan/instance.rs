@@ -1,32 +1,37 @@
 use anyhow::Error;
 use ash::{khr, vk};
 
-use crate::config::app::AppConfig;
+use crate::config::{app::AppConfig, vulkan::DevicePreference};
 
-const REQUIRED_EXTENSION_NAMES: [*const i8; 1] = [khr::surface::NAME.as_ptr()];
+use super::error::RendererInitError;
+
+const REQUIRED_EXTENSION_NAMES: [&std::ffi::CStr; 1] = [khr::surface::NAME];
 
 #[cfg(target_os = "linux")]
-const REQUIRED_PLATFORM_EXTENSION_NAMES: [*const i8; 2] = [
-  khr::wayland_surface::NAME.as_ptr(),
-  khr::xlib_surface::NAME.as_ptr(),
-];
+const REQUIRED_PLATFORM_EXTENSION_NAMES: [&std::ffi::CStr; 2] =
+  [khr::wayland_surface::NAME, khr::xlib_surface::NAME];
 
 #[cfg(target_os = "windows")]
-const REQUIRED_PLATFORM_EXTENSION_NAMES: [*const i8; 1] = [khr::win32_surface::NAME.as_ptr()];
+const REQUIRED_PLATFORM_EXTENSION_NAMES: [&std::ffi::CStr; 1] = [khr::win32_surface::NAME];
 
 pub(crate) struct InstanceDevice {
   instance: ash::Instance,
   physical_device: vk::PhysicalDevice,
+  physical_device_properties: vk::PhysicalDeviceProperties,
+  enabled_extensions: Vec<std::ffi::CString>,
 }
 
 impl InstanceDevice {
   pub(crate) fn init(config: &mut InstanceDeviceConfig, entry: &ash::Entry, app_config: &AppConfig) -> Result<Self, Error> {
-    let instance = InstanceDevice::init_instance(entry, config, app_config)?;
-    let (physical_device, _) = InstanceDevice::init_physical_device_and_properties(&instance)?;
+    let (instance, enabled_extensions) = InstanceDevice::init_instance(entry, config, app_config)?;
+    let (physical_device, physical_device_properties) =
+      InstanceDevice::init_physical_device_and_properties(&instance, config.device_preference)?;
 
     Ok(Self {
       instance,
       physical_device,
+      physical_device_properties,
+      enabled_extensions,
     })
   }
 
@@ -38,11 +43,20 @@ impl InstanceDevice {
     self.physical_device
   }
 
+  pub(crate) fn get_physical_device_properties(&self) -> &vk::PhysicalDeviceProperties {
+    &self.physical_device_properties
+  }
+
+  /// Required extensions plus the optional ones the driver supports
+  pub(crate) fn enabled_extensions(&self) -> &[std::ffi::CString] {
+    &self.enabled_extensions
+  }
+
   fn init_instance(
     entry: &ash::Entry,
     config: &mut InstanceDeviceConfig,
     app_config: &AppConfig
-  ) -> Result<ash::Instance, Error> {
+  ) -> Result<(ash::Instance, Vec<std::ffi::CString>), Error> {
     let engine_name = std::ffi::CString::new("Vulkan Game Engine")?;
     let app_name = std::ffi::CString::new(app_config.title.clone())?;
 
@@ -59,13 +73,21 @@ impl InstanceDevice {
       .map(|layer_name| layer_name.as_ptr())
       .collect();
 
-    let mut extension_name_ptrs: Vec<*const i8> = config
-      .extension_names
+    let available_extensions = unsafe { entry.enumerate_instance_extension_properties(None) }?;
+    let mut extension_names = config.extension_names.clone();
+    extension_names.extend(REQUIRED_EXTENSION_NAMES);
+    extension_names.extend(REQUIRED_PLATFORM_EXTENSION_NAMES);
+    let optional_extension_names = supported_extensions(
+      &config.optional_extension_names,
+      &extension_names,
+      &available_extensions,
+    );
+    extension_names.extend(optional_extension_names);
+
+    let extension_name_ptrs: Vec<*const i8> = extension_names
       .iter()
       .map(|extension_name| extension_name.as_ptr())
       .collect();
-    extension_name_ptrs.extend(REQUIRED_EXTENSION_NAMES.iter());
-    extension_name_ptrs.extend(REQUIRED_PLATFORM_EXTENSION_NAMES.iter());
 
     let mut instance_create_info = vk::InstanceCreateInfo::default()
       .application_info(&app_info)
@@ -76,26 +98,32 @@ impl InstanceDevice {
       instance_create_info = instance_create_info.push_next(info.as_mut());
     }
 
-    Ok(unsafe { entry.create_instance(&instance_create_info, None) }?)
+    let instance = unsafe { entry.create_instance(&instance_create_info, None) }?;
+    let enabled_extensions = extension_names
+      .into_iter()
+      .map(|name| name.to_owned())
+      .collect();
+
+    Ok((instance, enabled_extensions))
   }
 
   fn init_physical_device_and_properties(
     instance: &ash::Instance,
-  ) -> Result<(vk::PhysicalDevice, vk::PhysicalDeviceProperties), vk::Result> {
+    preference: DevicePreference,
+  ) -> Result<(vk::PhysicalDevice, vk::PhysicalDeviceProperties), Error> {
     let phys_devices = unsafe { instance.enumerate_physical_devices() }?;
+    let properties: Vec<_> = phys_devices
+      .iter()
+      .map(|p| unsafe { instance.get_physical_device_properties(*p) })
+      .collect();
+    let device_types: Vec<_> = properties
+      .iter()
+      .map(|properties| properties.device_type)
+      .collect();
 
-    let mut physical_device = None;
-    for p in phys_devices {
-      let properties = unsafe { instance.get_physical_device_properties(p) };
-      //let features = unsafe { instance.get_physical_device_features(p) };
-      if properties.device_type == vk::PhysicalDeviceType::DISCRETE_GPU {
-        physical_device = Some((p, properties));
-        break;
-      } else if properties.device_type == vk::PhysicalDeviceType::INTEGRATED_GPU {
-        physical_device = Some((p, properties));
-      }
-    }
-    Ok(physical_device.unwrap())
+    let index = select_physical_device(&device_types, preference)
+      .ok_or(RendererInitError::PhysicalDeviceMissing)?;
+    Ok((phys_devices[index], properties[index]))
   }
 
   pub(crate) fn destroy(&self) {
@@ -109,6 +137,8 @@ impl InstanceDevice {
 pub(crate) struct InstanceDeviceConfig<'a> {
   layer_names: Vec<&'a std::ffi::CStr>,
   extension_names: Vec<&'a std::ffi::CStr>,
+  optional_extension_names: Vec<&'a std::ffi::CStr>,
+  device_preference: DevicePreference,
   instance_next: Vec<Box<dyn vk::ExtendsInstanceCreateInfo + Send>>,
 }
 
@@ -137,6 +167,18 @@ impl<'a> InstanceDeviceConfig<'a> {
     self
   }
 
+  pub(crate) fn add_optional_extensions(mut self, extensions: Vec<&'a std::ffi::CStr>) -> Self {
+    for extension in extensions {
+      self.optional_extension_names.push(extension);
+    }
+    self
+  }
+
+  pub(crate) fn set_device_preference(mut self, device_preference: DevicePreference) -> Self {
+    self.device_preference = device_preference;
+    self
+  }
+
   pub(crate) fn add_instance_next(mut self, next: Box<dyn vk::ExtendsInstanceCreateInfo + Send>) -> Self {
     self.instance_next.push(next);
     self
@@ -152,3 +194,117 @@ impl<'a> InstanceDeviceConfig<'a> {
     self
   }
 }
+
+/// Index of the best ranked device type, the first listed device wins ties
+pub(crate) fn select_physical_device(
+  device_types: &[vk::PhysicalDeviceType],
+  preference: DevicePreference,
+) -> Option<usize> {
+  // max_by_key returns the last maximum, reversing keeps the first listed device on ties
+  device_types
+    .iter()
+    .enumerate()
+    .rev()
+    .max_by_key(|(_, device_type)| preference.rank(**device_type))
+    .map(|(index, _)| index)
+}
+
+/// Filters the optional extensions down to the ones present in the available extension properties,
+/// skipping the ones that are already required
+pub(crate) fn supported_extensions<'a>(
+  optional: &[&'a std::ffi::CStr],
+  required: &[&std::ffi::CStr],
+  available: &[vk::ExtensionProperties],
+) -> Vec<&'a std::ffi::CStr> {
+  optional
+    .iter()
+    .filter(|extension| !required.iter().any(|name| name == *extension))
+    .filter(|extension| {
+      available
+        .iter()
+        .any(|properties| properties.extension_name_as_c_str() == Ok(**extension))
+    })
+    .copied()
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use ash::{ext, khr, vk};
+
+  use super::{select_physical_device, supported_extensions};
+  use crate::config::vulkan::DevicePreference;
+
+  fn available(names: &[&std::ffi::CStr]) -> Vec<vk::ExtensionProperties> {
+    names
+      .iter()
+      .map(|name| {
+        vk::ExtensionProperties::default()
+          .extension_name(name)
+          .unwrap()
+      })
+      .collect()
+  }
+
+  #[test]
+  fn keeps_only_available_optional_extensions() {
+    let available = available(&[khr::surface::NAME, ext::debug_utils::NAME]);
+
+    let enabled = supported_extensions(
+      &[ext::debug_utils::NAME, khr::get_surface_capabilities2::NAME],
+      &[khr::surface::NAME],
+      &available,
+    );
+
+    assert_eq!(enabled, vec![ext::debug_utils::NAME]);
+  }
+
+  #[test]
+  fn skips_optional_extensions_that_are_required() {
+    let available = available(&[khr::surface::NAME, ext::debug_utils::NAME]);
+
+    let enabled = supported_extensions(
+      &[khr::surface::NAME, ext::debug_utils::NAME],
+      &[khr::surface::NAME],
+      &available,
+    );
+
+    assert_eq!(enabled, vec![ext::debug_utils::NAME]);
+  }
+
+  #[test]
+  fn selects_preferred_device_type() {
+    let device_types = [
+      vk::PhysicalDeviceType::CPU,
+      vk::PhysicalDeviceType::INTEGRATED_GPU,
+      vk::PhysicalDeviceType::DISCRETE_GPU,
+    ];
+
+    let selected = |preference| select_physical_device(&device_types, preference);
+    assert_eq!(selected(DevicePreference::Discrete), Some(2));
+    assert_eq!(selected(DevicePreference::Integrated), Some(1));
+    assert_eq!(selected(DevicePreference::Software), Some(0));
+  }
+
+  #[test]
+  fn first_device_wins_ties() {
+    let device_types = [
+      vk::PhysicalDeviceType::INTEGRATED_GPU,
+      vk::PhysicalDeviceType::DISCRETE_GPU,
+      vk::PhysicalDeviceType::DISCRETE_GPU,
+    ];
+
+    assert_eq!(
+      select_physical_device(&device_types, DevicePreference::Discrete),
+      Some(1)
+    );
+  }
+
+  #[test]
+  fn no_devices_selects_none() {
+    assert_eq!(
+      select_physical_device(&[], DevicePreference::Discrete),
+      None
+    );
+  }
+}
@@ -9,12 +9,20 @@ use instance::{InstanceDevice, InstanceDeviceConfig};
 use surface::Surface;
 use winit::window::Window;
 
-use crate::config::{app::AppConfig, vulkan::VulkanConfig};
+use crate::{
+  config::{
+    app::AppConfig,
+    vulkan::{DevicePreference, VulkanConfig},
+  },
+  engine::RendererInfo,
+};
 
 mod debug;
 mod device;
 pub mod error;
 mod graphics;
+#[cfg(test)]
+mod headless;
 mod instance;
 mod surface;
 
@@ -28,6 +36,7 @@ pub(crate) struct Vulkan {
   device: Device,
   renderer: Renderer,
   allocator: ManuallyDrop<vulkan::Allocator>,
+  destroyed: DestroyOnce,
 }
 
 impl Vulkan {
@@ -43,6 +52,10 @@ impl Vulkan {
     let mut instance_config = InstanceDeviceConfig::default()
       .add_layers(config.renderer.layers.clone())
       .add_extensions(config.renderer.instance_extensions.clone())
+      .add_optional_extensions(config.renderer.optional_instance_extensions.clone())
+      .set_device_preference(
+        DevicePreference::from_env().unwrap_or(config.renderer.device_preference),
+      )
       .add_instance_nexts(std::mem::take(&mut config.renderer.instance_next));
 
     let instance = InstanceDevice::init(&mut instance_config, &entry, app_config)?;
@@ -58,6 +71,11 @@ impl Vulkan {
     };
 
     let surface = Surface::init(&entry, instance.get_instance(), &window)?;
+    Device::enable_required_features(
+      instance.get_instance(),
+      instance.get_physical_device(),
+      &mut config,
+    )?;
     let device = Device::init(
       instance.get_instance(),
       instance.get_physical_device(),
@@ -85,14 +103,28 @@ impl Vulkan {
       device,
       renderer,
       allocator: ManuallyDrop::new(allocator),
+      destroyed: DestroyOnce::default(),
     })
   }
 
+  pub(crate) fn renderer_info(&self) -> RendererInfo {
+    RendererInfo::new(
+      self.instance.enabled_extensions().to_vec(),
+      self.device.enabled_extensions().to_vec(),
+      self.renderer.get_swap_chain_image_count(),
+    )
+  }
+
   pub(crate) fn request_redraw(&self) {
     self.window.request_redraw();
   }
 
+  /// Tears everything down in dependency order, also done on drop if not called before
   pub(crate) fn destroy(&mut self) {
+    if !self.destroyed.first() {
+      return;
+    }
+
     self.renderer.destroy(self.device.get_device(), &mut self.allocator);
     unsafe {
       ManuallyDrop::drop(&mut self.allocator);
@@ -105,3 +137,33 @@ impl Vulkan {
     self.instance.destroy();
   }
 }
+
+impl Drop for Vulkan {
+  fn drop(&mut self) {
+    self.destroy();
+  }
+}
+
+/// Guards a teardown that may run from both an explicit `destroy` and `Drop`
+#[derive(Default)]
+struct DestroyOnce(bool);
+
+impl DestroyOnce {
+  /// True only on the first call, later calls must not free anything again
+  fn first(&mut self) -> bool {
+    !std::mem::replace(&mut self.0, true)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::DestroyOnce;
+
+  #[test]
+  fn destroy_once_is_only_first_once() {
+    let mut destroyed = DestroyOnce::default();
+    assert!(destroyed.first());
+    assert!(!destroyed.first());
+    assert!(!destroyed.first());
+  }
+}